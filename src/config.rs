@@ -0,0 +1,336 @@
+use crate::leetcode::{Credentials, DEFAULT_BASE_URL};
+use chrono::{NaiveTime, Weekday};
+use reqwest::Url;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::fs;
+use teloxide::types::ChatId;
+use tokio::time::Duration;
+
+// Settings read from the config file and the environment at startup
+pub struct Config {
+    pub bot_token: String,
+    pub trigger_time: NaiveTime,
+    pub chat_ids_file_path: Option<String>,
+    // Extra attempts at reading CHAT_IDS_FILE_PATH at startup when it exists but can't be read
+    pub chat_ids_load_retries: u32,
+    pub database_url: Option<String>,
+    pub send_jitter: Duration,
+    pub send_concurrency: usize,
+    pub leetcode_base_url: String,
+    pub leetcode_user_agent: Option<String>,
+    // Cookies of a premium LeetCode account, never logged
+    pub leetcode_credentials: Option<Credentials>,
+    pub leetcode_timeout: Duration,
+    pub weekly_day: Weekday,
+    pub weekly_time: NaiveTime,
+    pub reminder_delay: Duration,
+    // How long the scheduler keeps asking LeetCode for a daily that isn't available yet
+    pub daily_retry_window: Duration,
+    // How often the problem index for random picks is fetched again, zero to only fetch it on /refreshcache
+    pub problem_index_refresh: Duration,
+    pub admin_chat_id: Option<ChatId>,
+    pub log_channel_id: Option<ChatId>,
+    #[cfg(feature = "discord")]
+    pub discord_webhook_url: Option<String>,
+    pub health_port: Option<u16>,
+    pub dry_run: bool,
+    pub send_repeated_daily: bool,
+    pub prune_on_startup: bool,
+    pub quiet_start: bool,
+    pub preview_chars: usize,
+    // Telegram HTML appended to every daily message, none if MESSAGE_FOOTER is empty
+    pub message_footer: Option<String>,
+    pub max_subscribers: Option<usize>,
+    pub bot_mode: BotMode,
+}
+
+// How the bot receives updates from Telegram
+pub enum BotMode {
+    // Long polling, works without a public endpoint
+    Polling,
+    // Telegram posts updates to `url`, which must be a public HTTPS endpoint forwarded to `port`
+    Webhook {
+        url: Url,
+        port: u16,
+        secret_token: Option<String>,
+    },
+}
+
+// Every problem found in the environment, so they can be fixed in one go
+#[derive(Debug)]
+pub struct ConfigError {
+    problems: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid configuration:")?;
+        for problem in &self.problems {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// Config file read unless CONFIG_FILE points elsewhere; it's optional at the default location
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+impl Config {
+    // Read and validate the config file and environment variables, collecting every problem instead of stopping at the first
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut reader = EnvReader::default();
+        reader.read_file();
+        let bot_token = reader.required("TELOXIDE_TOKEN");
+        let trigger_time = reader.required("TRIGGER_TIME").and_then(|time| {
+            reader.check("TRIGGER_TIME", "a time in the format HH:MM:SS", parse_time(&time))
+        });
+        let chat_ids_file_path = reader.var("CHAT_IDS_FILE_PATH");
+        let database_url = reader.var("DATABASE_URL");
+        if chat_ids_file_path.is_none() && database_url.is_none() {
+            reader.problems.push("either CHAT_IDS_FILE_PATH or DATABASE_URL must be set".to_string());
+        }
+        let chat_ids_load_retries =
+            reader.optional("CHAT_IDS_LOAD_RETRIES", "a number of retries", 3, |retries| retries.parse().ok());
+        let send_jitter = reader.optional("SEND_JITTER_SECS", "a number of seconds", 600, |secs| secs.parse().ok());
+        let send_concurrency = reader.optional("SEND_CONCURRENCY", "a positive number", 10, |count| {
+            count.parse().ok().filter(|count| *count > 0)
+        });
+        let leetcode_base_url = reader.optional(
+            "LEETCODE_BASE_URL",
+            "an http(s) URL, e.g. https://leetcode.cn",
+            DEFAULT_BASE_URL.to_string(),
+            |url| (url.starts_with("https://") || url.starts_with("http://")).then(|| url.to_string()),
+        );
+        let leetcode_user_agent = reader.var("LEETCODE_USER_AGENT");
+        let leetcode_session = reader.var("LEETCODE_SESSION").filter(|session| !session.trim().is_empty());
+        let leetcode_csrf = reader.var("LEETCODE_CSRF").filter(|csrf| !csrf.trim().is_empty());
+        if leetcode_csrf.is_some() && leetcode_session.is_none() {
+            reader.problems.push("LEETCODE_CSRF is only used together with LEETCODE_SESSION".to_string());
+        }
+        let leetcode_credentials = leetcode_session.map(|session| Credentials { session, csrf: leetcode_csrf });
+        let leetcode_timeout = reader.optional("LEETCODE_TIMEOUT_SECS", "a number of seconds", 15, |secs| secs.parse().ok());
+        let weekly_day = reader.optional("WEEKLY_DIGEST_DAY", "a weekday, e.g. Sun", Weekday::Sun, |day| day.parse().ok());
+        let weekly_time = reader.optional(
+            "WEEKLY_DIGEST_TIME",
+            "a time in the format HH:MM:SS",
+            NaiveTime::from_hms_opt(18, 0, 0).expect("valid time"),
+            parse_time,
+        );
+        let reminder_delay_hours = reader.optional("REMINDER_DELAY_HOURS", "a number of hours between 1 and 23", 4, |hours| {
+            hours.parse().ok().filter(|hours| (1..=23).contains(hours))
+        });
+        let daily_retry_minutes = reader.optional("DAILY_RETRY_MINUTES", "a number of minutes", 15, |minutes| minutes.parse().ok());
+        let problem_index_hours =
+            reader.optional("PROBLEM_INDEX_REFRESH_HOURS", "a number of hours", 24, |hours| hours.parse().ok());
+        let admin_chat_id = reader.optional("ADMIN_CHAT_ID", "a chat ID", None, |id| id.parse().ok().map(|id| Some(ChatId(id))));
+        let log_channel_id = reader.optional("LOG_CHANNEL_ID", "a chat ID", None, |id| id.parse().ok().map(|id| Some(ChatId(id))));
+        let discord_webhook_url = reader.var("DISCORD_WEBHOOK_URL");
+        if cfg!(not(feature = "discord")) && discord_webhook_url.is_some() {
+            reader.problems.push("DISCORD_WEBHOOK_URL needs the bot built with --features discord".to_string());
+        }
+        let health_port = reader.optional("HEALTH_PORT", "a port number", None, |port| port.parse().ok().map(Some));
+        let dry_run = reader.optional("DRY_RUN", "true or false", false, parse_bool);
+        let send_repeated_daily = reader.optional("SEND_REPEATED_DAILY", "true or false", false, parse_bool);
+        let prune_on_startup = reader.optional("PRUNE_ON_STARTUP", "true or false", false, parse_bool);
+        let quiet_start = reader.optional("QUIET_START", "true or false", false, parse_bool);
+        let preview_chars = reader.optional("PREVIEW_CHARS", "a number between 1 and 3000", 300, |chars| {
+            chars.parse().ok().filter(|chars| (1..=3000).contains(chars))
+        });
+        let message_footer = reader.var("MESSAGE_FOOTER").filter(|footer| !footer.trim().is_empty());
+        let max_subscribers = reader.optional("MAX_SUBSCRIBERS", "a positive number", None, |max| {
+            max.parse().ok().filter(|max| *max > 0).map(Some)
+        });
+        let bot_mode = match reader.optional("BOT_MODE", "polling or webhook", "polling".to_string(), |mode| {
+            ["polling", "webhook"].contains(&mode).then(|| mode.to_string())
+        })
+        .as_str()
+        {
+            "webhook" => {
+                let url = reader.required("WEBHOOK_URL").and_then(|url| {
+                    let parsed = Url::parse(url.trim()).ok().filter(|url| url.scheme() == "https");
+                    reader.check("WEBHOOK_URL", "a public https URL", parsed)
+                });
+                let port = reader.optional("WEBHOOK_PORT", "a port number", 8443, |port| port.parse().ok());
+                let secret_token = reader.var("WEBHOOK_SECRET_TOKEN");
+                url.map(|url| BotMode::Webhook { url, port, secret_token })
+            }
+            _ => Some(BotMode::Polling),
+        };
+
+        reader.check_unknown_keys();
+
+        match (bot_token, trigger_time, bot_mode) {
+            (Some(bot_token), Some(trigger_time), Some(bot_mode)) if reader.problems.is_empty() => Ok(Config {
+                bot_token,
+                trigger_time,
+                chat_ids_file_path,
+                chat_ids_load_retries,
+                database_url,
+                send_jitter: Duration::from_secs(send_jitter),
+                send_concurrency,
+                leetcode_base_url,
+                leetcode_user_agent,
+                leetcode_credentials,
+                leetcode_timeout: Duration::from_secs(leetcode_timeout),
+                weekly_day,
+                weekly_time,
+                reminder_delay: Duration::from_secs(reminder_delay_hours * 3600),
+                daily_retry_window: Duration::from_secs(daily_retry_minutes * 60),
+                problem_index_refresh: Duration::from_secs(problem_index_hours * 3600),
+                admin_chat_id,
+                log_channel_id,
+                #[cfg(feature = "discord")]
+                discord_webhook_url,
+                health_port,
+                dry_run,
+                send_repeated_daily,
+                prune_on_startup,
+                quiet_start,
+                preview_chars,
+                message_footer,
+                max_subscribers,
+                bot_mode,
+            }),
+            _ => Err(ConfigError { problems: reader.problems }),
+        }
+    }
+}
+
+// Reads settings while remembering what was missing or malformed; environment variables override the config file
+#[derive(Default)]
+struct EnvReader {
+    problems: Vec<String>,
+    // Settings from the config file, keyed by their environment variable name
+    file: HashMap<String, String>,
+    // Config file the settings came from, for error messages
+    file_path: Option<String>,
+    // Every setting that was looked up, so unknown keys in the file can be reported
+    known: HashSet<String>,
+}
+
+impl EnvReader {
+    // Load the config file from CONFIG_FILE, or config.toml if it exists
+    fn read_file(&mut self) {
+        let (path, required) = match env::var("CONFIG_FILE") {
+            Ok(path) => (path, true),
+            Err(_) => (DEFAULT_CONFIG_FILE.to_string(), false),
+        };
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound && !required => return,
+            Err(err) => {
+                self.problems.push(format!("config file {} can't be read: {}", path, err));
+                return;
+            }
+        };
+        match parse_config_file(&data) {
+            Ok(file) => self.file = file,
+            Err(problem) => self.problems.push(format!("config file {} is invalid: {}", path, problem)),
+        }
+        self.file_path = Some(path);
+    }
+
+    // Value of a setting, from the environment or else the config file
+    fn var(&mut self, name: &str) -> Option<String> {
+        self.known.insert(name.to_string());
+        env::var(name).ok().or_else(|| self.file.get(name).cloned())
+    }
+
+    // Report keys in the config file that no setting uses, most likely typos
+    fn check_unknown_keys(&mut self) {
+        let mut unknown: Vec<&String> = self.file.keys().filter(|name| !self.known.contains(*name)).collect();
+        unknown.sort();
+        for name in unknown {
+            self.problems.push(format!(
+                "config file {} has unknown setting {}",
+                self.file_path.as_deref().unwrap_or(DEFAULT_CONFIG_FILE),
+                name.to_lowercase()
+            ));
+        }
+    }
+
+    fn required(&mut self, name: &str) -> Option<String> {
+        match self.var(name) {
+            Some(value) if !value.trim().is_empty() => Some(value),
+            _ => {
+                self.problems.push(format!("{} is not set", name));
+                None
+            }
+        }
+    }
+
+    // Parse an optional variable, using the default when it's unset
+    fn optional<T>(&mut self, name: &str, expected: &str, default: T, parse: impl FnOnce(&str) -> Option<T>) -> T {
+        match self.var(name) {
+            Some(value) => {
+                let parsed = parse(value.trim());
+                self.check(name, expected, parsed).unwrap_or(default)
+            }
+            None => default,
+        }
+    }
+
+    fn check<T>(&mut self, name: &str, expected: &str, parsed: Option<T>) -> Option<T> {
+        if parsed.is_none() {
+            self.problems.push(format!("{} should be {}", name, expected));
+        }
+        parsed
+    }
+}
+
+// Flatten the config file into settings named like their environment variables, e.g. trigger_time becomes TRIGGER_TIME
+fn parse_config_file(data: &str) -> Result<HashMap<String, String>, String> {
+    let table: toml::Table = data.parse().map_err(|err: toml::de::Error| err.message().to_string())?;
+    table
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                toml::Value::String(value) => value,
+                toml::Value::Integer(value) => value.to_string(),
+                toml::Value::Float(value) => value.to_string(),
+                toml::Value::Boolean(value) => value.to_string(),
+                _ => return Err(format!("{} should be a string, number or boolean", key)),
+            };
+            Ok((key.to_uppercase(), value))
+        })
+        .collect()
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" => Some(true),
+        "false" | "0" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_time(time: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H:%M:%S").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_file_keys_are_named_like_environment_variables() {
+        let file = parse_config_file(
+            "trigger_time = \"10:00:02\"\nsend_jitter_secs = 60\ndry_run = true\nleetcode_base_url = \"https://leetcode.cn\"",
+        )
+        .unwrap();
+        assert_eq!(file["TRIGGER_TIME"], "10:00:02");
+        assert_eq!(file["SEND_JITTER_SECS"], "60");
+        assert_eq!(file["DRY_RUN"], "true");
+        assert_eq!(file["LEETCODE_BASE_URL"], "https://leetcode.cn");
+    }
+
+    #[test]
+    fn config_file_rejects_tables_and_invalid_toml() {
+        assert!(parse_config_file("[leetcode]\nbase_url = \"https://leetcode.cn\"").is_err());
+        assert!(parse_config_file("trigger_time = ").is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use teloxide::{DownloadError, RequestError};
+
+// Why a daily run or one of its steps failed, by kind, so callers can tell an outage that may pass from a broken query
+#[derive(Debug, thiserror::Error)]
+pub enum BotError {
+    // No usable HTTP answer from LeetCode: a network problem, a timeout, an error status or a body that isn't JSON
+    #[error("LeetCode request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    // JSON that doesn't have the fields the GraphQL query asked for
+    #[error("unexpected LeetCode response: {0}")]
+    Parse(String),
+    #[error("Telegram request failed: {0}")]
+    Telegram(#[from] RequestError),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("storage error: {0}")]
+    Storage(Box<dyn std::error::Error + Send + Sync>),
+    // A notifier outside Telegram couldn't post, e.g. to a Discord webhook
+    #[cfg(feature = "discord")]
+    #[error("webhook request failed: {0}")]
+    Webhook(#[source] reqwest::Error),
+}
+
+impl BotError {
+    // Whether trying again later may work; a rejected request or a response that can't be parsed won't change
+    pub fn is_transient(&self) -> bool {
+        match self {
+            BotError::Http(err) => crate::leetcode::is_transient(err),
+            #[cfg(feature = "discord")]
+            BotError::Webhook(err) => crate::leetcode::is_transient(err),
+            BotError::Telegram(err) => is_transient(err),
+            BotError::Parse(_) | BotError::Io(_) | BotError::Storage(_) => false,
+        }
+    }
+}
+
+// Whether a failed Telegram request may work when tried again, as opposed to Telegram rejecting it for good
+pub fn is_transient(err: &RequestError) -> bool {
+    matches!(err, RequestError::Network(_) | RequestError::RetryAfter(_) | RequestError::Io(_))
+}
+
+impl From<DownloadError> for BotError {
+    fn from(err: DownloadError) -> Self {
+        match err {
+            DownloadError::Network(err) => BotError::Telegram(RequestError::Network(err)),
+            DownloadError::Io(err) => BotError::Io(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn http_error(status: u16) -> BotError {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).respond_with(ResponseTemplate::new(status)).mount(&server).await;
+        let err = reqwest::get(server.uri()).await.unwrap().error_for_status().unwrap_err();
+        BotError::from(err)
+    }
+
+    #[tokio::test]
+    async fn only_server_errors_are_worth_retrying() {
+        assert!(http_error(503).await.is_transient());
+        let forbidden = http_error(403).await;
+        assert!(matches!(forbidden, BotError::Http(_)));
+        assert!(!forbidden.is_transient());
+    }
+
+    #[test]
+    fn schema_problems_are_not_worth_retrying() {
+        let err = BotError::Parse("no data in the response".to_string());
+        assert!(!err.is_transient());
+        assert_eq!(err.to_string(), "unexpected LeetCode response: no data in the response");
+
+        let err = BotError::from(RequestError::RetryAfter(std::time::Duration::from_secs(5)));
+        assert!(err.is_transient());
+        assert!(!BotError::from(RequestError::Api(teloxide::ApiError::BotBlocked)).is_transient());
+    }
+}
@@ -0,0 +1,74 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+// Liveness and readiness state reported to the container orchestrator
+#[derive(Default)]
+pub struct Health {
+    chats_loaded: AtomicBool,
+    scheduler_running: AtomicBool,
+    last_sent: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl Health {
+    pub fn mark_chats_loaded(&self) {
+        self.chats_loaded.store(true, Ordering::Relaxed);
+    }
+
+    pub fn set_scheduler_running(&self, running: bool) {
+        self.scheduler_running.store(running, Ordering::Relaxed);
+    }
+
+    // Remember when a daily run last finished successfully
+    pub fn record_send(&self, at: DateTime<Utc>) {
+        *self.last_sent.lock().unwrap() = Some(at);
+    }
+
+    fn is_ready(&self) -> bool {
+        self.chats_loaded.load(Ordering::Relaxed) && self.scheduler_running.load(Ordering::Relaxed)
+    }
+
+    fn report(&self, status: &str) -> Json<serde_json::Value> {
+        let last_sent = *self.last_sent.lock().unwrap();
+        Json(json!({
+            "status": status,
+            "last_sent": last_sent.map(|at| at.to_rfc3339()),
+        }))
+    }
+}
+
+async fn health(State(health): State<Arc<Health>>) -> impl IntoResponse {
+    (StatusCode::OK, health.report("ok"))
+}
+
+async fn ready(State(health): State<Arc<Health>>) -> impl IntoResponse {
+    if health.is_ready() {
+        (StatusCode::OK, health.report("ready"))
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, health.report("starting"))
+    }
+}
+
+// Serve /health, /ready and /metrics until shutdown is requested
+pub async fn serve(addr: SocketAddr, health: Arc<Health>, shutdown: CancellationToken) -> std::io::Result<()> {
+    let router = Router::new()
+        .route("/health", get(self::health))
+        .route("/ready", get(ready))
+        .route("/metrics", get(|| async { crate::metrics::render() }))
+        .with_state(health);
+    let listener = TcpListener::bind(addr).await?;
+    info!(%addr, "Health server listening.");
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async move { shutdown.cancelled().await })
+        .await
+}
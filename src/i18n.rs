@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+
+// Languages the bot's messages are available in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+    #[default]
+    En,
+    Es,
+}
+
+impl Lang {
+    pub const ALL: [Lang; 2] = [Lang::En, Lang::Es];
+
+    // Parse a language code such as "es", ignoring case
+    pub fn parse(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|lang| lang.code().eq_ignore_ascii_case(code.trim()))
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::En => "en",
+            Lang::Es => "es",
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Lang::default()
+    }
+}
+
+// Messages that are translated, looked up with `text`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    DailyHeader,
+    DailyLabel,
+    PickLabel,
+    FeaturedLabel,
+    BonusLabel,
+    NotAvailable,
+    RandomHeader,
+    Started,
+    AlreadySubscribed,
+    Stopped,
+    NotSubscribed,
+    TodayUnavailable,
+    LeetCodeUnreachable,
+    LanguageSet,
+    LanguageUsage,
+    Reminder,
+    OpenButton,
+    SolvedButton,
+    RandomButton,
+    ThreadAnchor,
+    UnknownCommand,
+}
+
+// Message for the key in the given language, falling back to English when it isn't translated
+pub fn text(lang: Lang, key: Key) -> &'static str {
+    let translated = match lang {
+        Lang::En => None,
+        Lang::Es => spanish(key),
+    };
+    translated.unwrap_or_else(|| english(key))
+}
+
+fn english(key: Key) -> &'static str {
+    match key {
+        Key::DailyHeader => "Today's LeetCode Challenge:",
+        Key::DailyLabel => "Daily",
+        Key::PickLabel => "Pick",
+        Key::FeaturedLabel => "Featured",
+        Key::BonusLabel => "Bonus",
+        Key::NotAvailable => "Not available",
+        Key::RandomHeader => "Random LeetCode Problem:",
+        Key::Started => "You will start receiving daily challenges. Send /help to see all commands.",
+        Key::AlreadySubscribed => "This chat is already subscribed. Send /status to see its settings.",
+        Key::Stopped => "You have stopped receiving daily challenges.",
+        Key::NotSubscribed => "Subscribe with /start before changing settings.",
+        Key::TodayUnavailable => "Sorry, today's challenge is not available right now. Please try again later.",
+        Key::LeetCodeUnreachable => "Sorry, I couldn't reach LeetCode right now. Please try again later.",
+        Key::LanguageSet => "Messages in this chat are now in English.",
+        Key::LanguageUsage => "Unknown language. Supported languages:",
+        Key::Reminder => "⏰ Nobody has marked today's problem as solved yet. Send /solved once you're done!",
+        Key::OpenButton => "Open Problem",
+        Key::SolvedButton => "✅ Mark Solved",
+        Key::RandomButton => "🎲 Random Problem",
+        Key::ThreadAnchor => "📌 Daily LeetCode challenges, each day's problem is posted as a reply to this message.",
+        Key::UnknownCommand => "Unknown command, try /help.",
+    }
+}
+
+fn spanish(key: Key) -> Option<&'static str> {
+    Some(match key {
+        Key::DailyHeader => "Desafío de LeetCode de hoy:",
+        Key::DailyLabel => "Diario",
+        Key::PickLabel => "Elegido",
+        Key::FeaturedLabel => "Destacado",
+        Key::BonusLabel => "Extra",
+        Key::NotAvailable => "No disponible",
+        Key::RandomHeader => "Problema aleatorio de LeetCode:",
+        Key::Started => "Empezarás a recibir los desafíos diarios. Envía /help para ver todos los comandos.",
+        Key::AlreadySubscribed => "Este chat ya está suscrito. Envía /status para ver su configuración.",
+        Key::Stopped => "Has dejado de recibir los desafíos diarios.",
+        Key::NotSubscribed => "Suscríbete con /start antes de cambiar la configuración.",
+        Key::TodayUnavailable => "Lo siento, el desafío de hoy no está disponible ahora. Inténtalo más tarde.",
+        Key::LeetCodeUnreachable => "Lo siento, no pude conectar con LeetCode. Inténtalo más tarde.",
+        Key::LanguageSet => "Los mensajes de este chat ahora están en español.",
+        Key::LanguageUsage => "Idioma desconocido. Idiomas disponibles:",
+        Key::Reminder => "⏰ Nadie ha marcado el problema de hoy como resuelto todavía. ¡Envía /solved cuando lo termines!",
+        Key::OpenButton => "Abrir problema",
+        Key::SolvedButton => "✅ Resuelto",
+        Key::RandomButton => "🎲 Problema aleatorio",
+        Key::ThreadAnchor => "📌 Desafíos diarios de LeetCode, el problema de cada día se publica como respuesta a este mensaje.",
+        Key::UnknownCommand => "Comando desconocido, prueba /help.",
+    })
+}
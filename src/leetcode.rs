@@ -0,0 +1,1210 @@
+use crate::error::BotError;
+use crate::metrics::METRICS;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, COOKIE, ORIGIN, REFERER, USER_AGENT};
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use tokio::time::{sleep, Duration};
+use tracing::{debug, info, warn};
+
+// Problem difficulty as reported by LeetCode
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "easy" => Some(Difficulty::Easy),
+            "medium" => Some(Difficulty::Medium),
+            "hard" => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+
+    pub fn emoji(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "🟢",
+            Difficulty::Medium => "🟡",
+            Difficulty::Hard => "🔴",
+        }
+    }
+
+    // Value expected by the problem list `difficulty` filter
+    fn filter_value(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "EASY",
+            Difficulty::Medium => "MEDIUM",
+            Difficulty::Hard => "HARD",
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+        };
+        f.write_str(name)
+    }
+}
+
+// The daily question as extracted from the LeetCode response
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DailyQuestion {
+    pub title: String,
+    // Empty for history entries recorded before the slug was kept
+    #[serde(default)]
+    pub slug: String,
+    pub link: String,
+    pub difficulty: Option<Difficulty>,
+    // Percentage of accepted submissions
+    pub ac_rate: Option<f64>,
+    // Total submissions as formatted by LeetCode, e.g. "2.5M"
+    pub total_submissions: Option<String>,
+    // Topic tags such as Array or Two Pointers, empty if unknown
+    #[serde(default)]
+    pub tags: Vec<TopicTag>,
+    // Premium problems can only be opened with a LeetCode subscription
+    #[serde(default)]
+    pub paid_only: bool,
+    // Starter code per language, only fetched for the daily and not kept in the history
+    #[serde(default, skip_serializing)]
+    pub code_snippets: Vec<CodeSnippet>,
+    // Problem statement as plain text, only fetched for the daily and not kept in the history
+    #[serde(default, skip_serializing)]
+    pub content: Option<String>,
+    // Companies that asked the problem, most frequent first; only fetched for the daily with a premium session
+    #[serde(default, skip_serializing)]
+    pub companies: Vec<String>,
+    // How many hints LeetCode offers; their text isn't kept, so nothing can be spoiled
+    #[serde(default, skip_serializing)]
+    pub hints: usize,
+}
+
+// Turn LeetCode's HTML problem statement into plain text
+pub fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    // Collapse the blank lines left behind by removed block tags
+    text.lines().map(str::trim_end).filter(|line| !line.trim().is_empty()).collect::<Vec<_>>().join("\n")
+}
+
+// Starter code LeetCode shows for one language
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct CodeSnippet {
+    #[serde(rename = "langSlug")]
+    pub lang_slug: String,
+    pub lang: String,
+    pub code: String,
+}
+
+// Language slugs LeetCode uses for code snippets, with the shorthands users may type
+const CODE_LANGS: &[(&str, &[&str])] = &[
+    ("python3", &["python", "py"]),
+    ("cpp", &["c++"]),
+    ("java", &[]),
+    ("javascript", &["js"]),
+    ("typescript", &["ts"]),
+    ("golang", &["go"]),
+    ("rust", &["rs"]),
+    ("csharp", &["c#", "cs"]),
+    ("c", &[]),
+    ("kotlin", &["kt"]),
+    ("swift", &[]),
+    ("ruby", &["rb"]),
+    ("scala", &[]),
+    ("php", &[]),
+];
+
+// Resolve a language as typed by a user, e.g. "python", to LeetCode's snippet slug
+pub fn code_lang_slug(lang: &str) -> Option<&'static str> {
+    let lang = lang.trim().to_lowercase();
+    CODE_LANGS
+        .iter()
+        .find(|(slug, aliases)| *slug == lang || aliases.contains(&lang.as_str()))
+        .map(|(slug, _)| *slug)
+}
+
+// All supported language slugs, for help replies
+pub fn code_lang_slugs() -> impl Iterator<Item = &'static str> {
+    CODE_LANGS.iter().map(|(slug, _)| *slug)
+}
+
+// A LeetCode topic tag, identified by its slug in filters
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TopicTag {
+    pub name: String,
+    pub slug: String,
+}
+
+// Shorthands users commonly type for tags with longer slugs
+const TAG_ALIASES: &[(&str, &str)] = &[
+    ("dp", "dynamic-programming"),
+    ("bfs", "breadth-first-search"),
+    ("dfs", "depth-first-search"),
+    ("bit", "bit-manipulation"),
+    ("ll", "linked-list"),
+];
+
+// Turn a tag as typed by a user, e.g. "Two Pointers" or "dp", into its LeetCode slug
+pub fn tag_slug(tag: &str) -> String {
+    let slug = tag.trim().to_lowercase().split_whitespace().collect::<Vec<_>>().join("-");
+    TAG_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == slug)
+        .map_or(slug, |(_, full)| full.to_string())
+}
+
+impl DailyQuestion {
+    // leetcode.cn reports the acceptance rate as a fraction instead of a percentage
+    fn with_percent_ac_rate(mut self) -> Self {
+        self.ac_rate = self.ac_rate.map(|ac_rate| ac_rate * 100.0);
+        self
+    }
+
+    // Build the question from LeetCode's question object, deriving the link from the slug when none is given
+    fn from_raw(question: RawQuestion, link: Option<String>, base_url: &str) -> Self {
+        let path = link.unwrap_or_else(|| format!("/problems/{}/", question.title_slug));
+        // `stats` is a JSON document encoded as a string
+        let stats = question.stats.and_then(|stats| serde_json::from_str::<Value>(&stats).ok());
+        DailyQuestion {
+            title: question.title.unwrap_or_else(|| question.title_slug.clone()),
+            slug: question.title_slug,
+            link: format!("{}{}", base_url, path),
+            difficulty: question.difficulty.as_deref().and_then(Difficulty::parse),
+            ac_rate: question.ac_rate,
+            total_submissions: stats
+                .as_ref()
+                .and_then(|stats| stats.get("totalSubmission"))
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            tags: question
+                .topic_tags
+                .unwrap_or_default()
+                .into_iter()
+                .map(|tag| TopicTag {
+                    slug: tag.slug.unwrap_or_else(|| tag_slug(&tag.name)),
+                    name: tag.name,
+                })
+                .collect(),
+            paid_only: question.paid_only.unwrap_or(false),
+            code_snippets: question.code_snippets.unwrap_or_default(),
+            content: question.content.as_deref().map(strip_html),
+            companies: question
+                .company_tag_stats
+                .as_deref()
+                .map(|stats| {
+                    parse_company_tags(stats).unwrap_or_else(|err| {
+                        warn!(error = %err, "Malformed company tag stats, leaving them out.");
+                        Vec::new()
+                    })
+                })
+                .unwrap_or_default(),
+            hints: question.hints.map_or(0, |hints| hints.len()),
+        }
+    }
+}
+
+// GraphQL response envelope; `data` is missing when LeetCode rejects the query, e.g. after a schema change
+#[derive(Deserialize)]
+struct GraphQlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Deserialize)]
+struct GraphQlError {
+    message: String,
+}
+
+// `data` of the leetcode.com daily query
+#[derive(Deserialize)]
+struct DailyDataCom {
+    #[serde(rename = "activeDailyCodingChallengeQuestion")]
+    daily: Option<RawDaily>,
+}
+
+// `data` of the leetcode.cn daily query
+#[derive(Deserialize)]
+struct DailyDataCn {
+    #[serde(rename = "todayRecord")]
+    records: Option<Vec<RawDaily>>,
+}
+
+#[derive(Deserialize)]
+struct RawDaily {
+    #[serde(default, deserialize_with = "lenient")]
+    link: Option<String>,
+    question: RawQuestion,
+}
+
+// Question object as LeetCode sends it; only the slug is required, other fields of the wrong type are treated as missing
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawQuestion {
+    title_slug: String,
+    #[serde(default, deserialize_with = "lenient")]
+    title: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    difficulty: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    ac_rate: Option<f64>,
+    #[serde(default, deserialize_with = "lenient")]
+    stats: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    topic_tags: Option<Vec<RawTag>>,
+    #[serde(default, deserialize_with = "lenient")]
+    paid_only: Option<bool>,
+    #[serde(default, deserialize_with = "lenient")]
+    code_snippets: Option<Vec<CodeSnippet>>,
+    #[serde(default, deserialize_with = "lenient")]
+    content: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    company_tag_stats: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    hints: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct RawTag {
+    name: String,
+    slug: Option<String>,
+}
+
+// Deserialize an optional field, treating a value of the wrong type like a missing one
+fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let value = Value::deserialize(deserializer)?;
+    Ok(serde_json::from_value(value).ok())
+}
+
+// Parse the `data` of a GraphQL response, reporting a missing or malformed one as a schema problem
+fn parse_data<T: DeserializeOwned>(response: Value) -> Result<T, BotError> {
+    let response: GraphQlResponse<T> =
+        serde_json::from_value(response).map_err(|err| BotError::Parse(err.to_string()))?;
+    match response.data {
+        Some(data) => Ok(data),
+        None if response.errors.is_empty() => Err(BotError::Parse("no data in the response".to_string())),
+        None => {
+            let messages: Vec<String> = response.errors.into_iter().map(|error| error.message).collect();
+            Err(BotError::Parse(format!("no data in the response, errors: {}", messages.join("; "))))
+        }
+    }
+}
+
+// LeetCode site, leetcode.cn uses different GraphQL queries and response shapes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Site {
+    Com,
+    Cn,
+}
+
+impl Site {
+    fn from_base_url(base_url: &str) -> Self {
+        if base_url.contains("leetcode.cn") {
+            Site::Cn
+        } else {
+            Site::Com
+        }
+    }
+}
+
+// Site used unless LEETCODE_BASE_URL overrides it
+pub const DEFAULT_BASE_URL: &str = "https://leetcode.com";
+
+// Cookies of a logged-in LeetCode account, sent with every request to unlock premium-only data
+#[derive(Clone)]
+pub struct Credentials {
+    pub session: String,
+    // CSRF token, some endpoints reject authenticated requests without it
+    pub csrf: Option<String>,
+}
+
+// Keeps the credentials out of logs and panic messages
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("session", &"<redacted>")
+            .field("csrf", &self.csrf.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+// Header LeetCode expects the CSRF token in, next to the csrftoken cookie
+const CSRF_HEADER: &str = "x-csrftoken";
+
+// Build a header from a secret, leaving the value out of the error
+fn sensitive_header(value: &str, name: &str) -> Result<HeaderValue, String> {
+    let mut header =
+        HeaderValue::from_str(value).map_err(|_| format!("invalid {}, it may only contain visible ASCII characters", name))?;
+    header.set_sensitive(true);
+    Ok(header)
+}
+
+// HTTP client for one LeetCode site
+pub struct LeetCodeClient {
+    http: Client,
+    base_url: String,
+    site: Site,
+    // Whether requests carry a LeetCode session, needed for premium-only data
+    authenticated: bool,
+}
+
+impl LeetCodeClient {
+    pub fn authenticated(&self) -> bool {
+        self.authenticated
+    }
+}
+
+// Browser-like User-Agent used unless LEETCODE_USER_AGENT overrides it
+const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/58.0.3029.110 Safari/537.3";
+
+// Build the HTTP client used for all LeetCode requests
+pub fn build_leetcode_client(
+    base_url: &str,
+    user_agent: Option<&str>,
+    credentials: Option<&Credentials>,
+    timeout: Duration,
+) -> Result<LeetCodeClient, Box<dyn std::error::Error + Send + Sync>> {
+    let base_url = base_url.trim_end_matches('/');
+    let user_agent = user_agent.unwrap_or(DEFAULT_USER_AGENT);
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        ORIGIN,
+        HeaderValue::from_str(base_url).map_err(|err| format!("invalid LeetCode base URL {:?}: {}", base_url, err))?,
+    );
+    headers.insert(
+        USER_AGENT,
+        HeaderValue::from_str(user_agent).map_err(|err| format!("invalid LeetCode User-Agent {:?}: {}", user_agent, err))?,
+    );
+    if let Some(credentials) = credentials {
+        let mut cookie = format!("LEETCODE_SESSION={}", credentials.session);
+        if let Some(csrf) = &credentials.csrf {
+            cookie.push_str(&format!("; csrftoken={}", csrf));
+            headers.insert(HeaderName::from_static(CSRF_HEADER), sensitive_header(csrf, "LEETCODE_CSRF")?);
+            // LeetCode's CSRF check also wants the request to come from its own pages
+            headers.insert(REFERER, HeaderValue::from_str(base_url)?);
+        }
+        headers.insert(COOKIE, sensitive_header(&cookie, "LEETCODE_SESSION or LEETCODE_CSRF")?);
+    }
+    let http = Client::builder().default_headers(headers).timeout(timeout).build()?;
+    Ok(LeetCodeClient {
+        http,
+        base_url: base_url.to_string(),
+        site: Site::from_base_url(base_url),
+        authenticated: credentials.is_some(),
+    })
+}
+
+// Retries after the first failed LeetCode request, waiting 1s, 2s, 4s, ...
+const FETCH_RETRIES: u32 = 3;
+
+// Connection problems, timeouts and server errors are worth retrying, client errors are not
+pub fn is_transient(err: &reqwest::Error) -> bool {
+    err.is_connect() || err.is_timeout() || err.status().is_some_and(|status| status.is_server_error())
+}
+
+// Send a GraphQL request to LeetCode, retrying transient failures with exponential backoff
+async fn post_leetcode_graphql(client: &LeetCodeClient, query: &Value) -> Result<Value, reqwest::Error> {
+    let mut attempt = 0;
+    loop {
+        let result = async {
+            client
+                .http
+                .post(format!("{}/graphql/", client.base_url))
+                .json(query)
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<Value>()
+                .await
+        }
+        .await;
+
+        match result {
+            Err(err) if attempt < FETCH_RETRIES && is_transient(&err) => {
+                let delay = Duration::from_secs(1 << attempt);
+                attempt += 1;
+                warn!(
+                    error = %err,
+                    delay_secs = delay.as_secs(),
+                    retry = attempt,
+                    max_retries = FETCH_RETRIES,
+                    "LeetCode request failed, retrying"
+                );
+                sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+// Daily question queries; leetcode.cn calls it todayRecord and returns a list without a link
+const DAILY_QUERY_COM: &str = "query questionOfToday {activeDailyCodingChallengeQuestion {date link question {title titleSlug difficulty acRate stats topicTags {name slug} paidOnly: isPaidOnly codeSnippets {lang langSlug code} content hints}}}";
+const DAILY_QUERY_CN: &str = "query questionOfToday {todayRecord {date question {title titleSlug difficulty acRate stats topicTags {name slug} paidOnly: isPaidOnly codeSnippets {lang langSlug code} content hints}}}";
+
+// Daily query with premium-only fields, used on leetcode.com when a session is configured
+const DAILY_QUERY_COM_PREMIUM: &str = "query questionOfToday {activeDailyCodingChallengeQuestion {date link question {title titleSlug difficulty acRate stats topicTags {name slug} paidOnly: isPaidOnly codeSnippets {lang langSlug code} content hints companyTagStats}}}";
+
+// Fetch the daily LeetCode question, `None` if LeetCode has none today
+pub async fn fetch_leetcode_daily_question(client: &LeetCodeClient) -> Result<Option<DailyQuestion>, BotError> {
+    if client.authenticated && client.site == Site::Com {
+        match fetch_daily_with(client, DAILY_QUERY_COM_PREMIUM).await {
+            // The session may have expired or the premium fields changed, neither should cost the daily
+            Err(BotError::Parse(problem)) => {
+                warn!(problem, "Daily query with premium fields failed, retrying without them.");
+            }
+            result => return result,
+        }
+    }
+    let query = match client.site {
+        Site::Com => DAILY_QUERY_COM,
+        Site::Cn => DAILY_QUERY_CN,
+    };
+    fetch_daily_with(client, query).await
+}
+
+// Send one of the daily queries and parse its answer
+async fn fetch_daily_with(client: &LeetCodeClient, query: &str) -> Result<Option<DailyQuestion>, BotError> {
+    let query = json!({
+        "query": query,
+        "variables": {},
+        "operationName": "questionOfToday"
+    });
+    debug!(site = ?client.site, authenticated = client.authenticated, "Sending request to LeetCode for daily question...");
+    let timer = METRICS.fetch_latency.start_timer();
+    let response = post_leetcode_graphql(client, &query).await;
+    timer.observe_duration();
+    let response = response?;
+
+    debug!("Response from LeetCode arrived for daily question.");
+    let daily = match client.site {
+        Site::Com => parse_data::<DailyDataCom>(response)?.daily,
+        Site::Cn => parse_data::<DailyDataCn>(response)?.records.and_then(|records| records.into_iter().next()),
+    };
+    let daily_question = daily.map(|daily| {
+        let question = DailyQuestion::from_raw(daily.question, daily.link, &client.base_url);
+        match client.site {
+            Site::Com => question,
+            Site::Cn => question.with_percent_ac_rate(),
+        }
+    });
+    if let Some(daily_question) = &daily_question {
+        info!(title = %daily_question.title, "Daily question found.");
+    }
+    Ok(daily_question)
+}
+
+// Problem list queries, aliased so both sites return `{total questions}`
+const LIST_QUERY_COM: &str = "query problemsetQuestionList($categorySlug: String, $limit: Int, $skip: Int, $filters: QuestionListFilterInput) {problemsetQuestionList: questionList(categorySlug: $categorySlug, limit: $limit, skip: $skip, filters: $filters) {total: totalNum questions: data {title titleSlug difficulty acRate paidOnly: isPaidOnly topicTags {name slug}}}}";
+const LIST_QUERY_CN: &str = "query problemsetQuestionList($categorySlug: String, $limit: Int, $skip: Int, $filters: QuestionListFilterInput) {problemsetQuestionList(categorySlug: $categorySlug, limit: $limit, skip: $skip, filters: $filters) {total questions {title titleSlug difficulty acRate paidOnly topicTags {name slug}}}}";
+
+// Fetch one page of the problem list, returning the total number of matches and the page's questions
+async fn fetch_question_list(
+    client: &LeetCodeClient,
+    difficulty: Option<Difficulty>,
+    tag: Option<&str>,
+    skip: u64,
+    limit: u64,
+) -> Result<Option<(u64, Vec<Value>)>, BotError> {
+    let mut filters = json!({});
+    if let Some(difficulty) = difficulty {
+        filters["difficulty"] = json!(difficulty.filter_value());
+    }
+    if let Some(tag) = tag {
+        filters["tags"] = json!([tag]);
+    }
+    let query = json!({
+        "query": match client.site {
+            Site::Com => LIST_QUERY_COM,
+            Site::Cn => LIST_QUERY_CN,
+        },
+        "variables": {"categorySlug": "", "skip": skip, "limit": limit, "filters": filters},
+        "operationName": "problemsetQuestionList"
+    });
+    let response = post_leetcode_graphql(client, &query).await?;
+
+    let Some(list) = response.get("data").and_then(|data| data.get("problemsetQuestionList")) else {
+        return Ok(None);
+    };
+    let total = list.get("total").and_then(Value::as_u64).unwrap_or(0);
+    let questions = list.get("questions").and_then(Value::as_array).cloned().unwrap_or_default();
+    Ok(Some((total, questions)))
+}
+
+// Number of problems fetched around a random offset to choose a free one from
+const RANDOM_PAGE_SIZE: u64 = 50;
+
+// Pick a random element of the set, `None` if it's empty
+fn random_element<T: Clone>(set: &BTreeSet<T>) -> Option<T> {
+    if set.is_empty() {
+        return None;
+    }
+    let index = rand::thread_rng().gen_range(0..set.len());
+    set.iter().nth(index).cloned()
+}
+
+// Which problems a random pick may return
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ProblemFilter {
+    // Allowed difficulties, empty means any
+    pub difficulties: BTreeSet<Difficulty>,
+    // Tag slugs of which the problem needs at least one, empty means any
+    pub tags: BTreeSet<String>,
+    // Whether premium problems may be picked
+    pub include_paid: bool,
+}
+
+impl ProblemFilter {
+    // Whether a problem of the index may be picked, the same rule random picks from LeetCode follow
+    pub fn matches(&self, problem: &ProblemSummary) -> bool {
+        let difficulty_matches = self.difficulties.is_empty()
+            || problem.difficulty.is_some_and(|difficulty| self.difficulties.contains(&difficulty));
+        let tag_matches = self.tags.is_empty() || problem.tags.iter().any(|tag| self.tags.contains(tag));
+        difficulty_matches && tag_matches && (self.include_paid || !problem.paid_only)
+    }
+}
+
+// One problem of the problem index, only what random picks filter on
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProblemSummary {
+    pub slug: String,
+    pub title: String,
+    pub difficulty: Option<Difficulty>,
+    pub paid_only: bool,
+    // Tag slugs
+    pub tags: Vec<String>,
+}
+
+// Problems fetched per request when building the problem index
+const INDEX_PAGE_SIZE: u64 = 1000;
+
+// Fetch a summary of every problem, page by page
+pub async fn fetch_problem_index(
+    client: &LeetCodeClient,
+) -> Result<Vec<ProblemSummary>, BotError> {
+    let mut problems = Vec::new();
+    loop {
+        let skip = problems.len() as u64;
+        let Some((total, questions)) = fetch_question_list(client, None, None, skip, INDEX_PAGE_SIZE).await? else {
+            return Err(BotError::Parse("no problem list in the response".to_string()));
+        };
+        problems.extend(
+            questions
+                .into_iter()
+                .filter_map(|question| serde_json::from_value::<RawQuestion>(question).ok())
+                .map(|question| ProblemSummary {
+                    title: question.title.unwrap_or_else(|| question.title_slug.clone()),
+                    slug: question.title_slug,
+                    difficulty: question.difficulty.as_deref().and_then(Difficulty::parse),
+                    paid_only: question.paid_only.unwrap_or(false),
+                    tags: question
+                        .topic_tags
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|tag| tag.slug.unwrap_or_else(|| tag_slug(&tag.name)))
+                        .collect(),
+                }),
+        );
+        // Stop on a short page too, so a changing total can't keep the loop going
+        if problems.len() as u64 >= total || problems.len() as u64 - skip < INDEX_PAGE_SIZE {
+            break;
+        }
+    }
+    info!(problems = problems.len(), "Problem index fetched.");
+    Ok(problems)
+}
+
+// Fetch a random problem matching the filter
+pub async fn fetch_random_question(
+    client: &LeetCodeClient,
+    filter: &ProblemFilter,
+) -> Result<Option<DailyQuestion>, BotError> {
+    let difficulty = random_element(&filter.difficulties);
+    let tag = random_element(&filter.tags);
+    debug!(?difficulty, ?tag, "Sending request to LeetCode for a random question...");
+
+    let Some((total, _)) = fetch_question_list(client, difficulty, tag.as_deref(), 0, 1).await? else {
+        return Ok(None);
+    };
+    if total == 0 {
+        return Ok(None);
+    }
+    let skip = rand::thread_rng().gen_range(0..=total.saturating_sub(RANDOM_PAGE_SIZE));
+    let Some((_, questions)) = fetch_question_list(client, difficulty, tag.as_deref(), skip, RANDOM_PAGE_SIZE).await? else {
+        return Ok(None);
+    };
+
+    // Premium problems only lead free users to a paywall
+    let candidates: Vec<&Value> = questions
+        .iter()
+        .filter(|question| filter.include_paid || question.get("paidOnly").and_then(Value::as_bool) != Some(true))
+        .collect();
+    let question = if candidates.is_empty() {
+        None
+    } else {
+        let index = rand::thread_rng().gen_range(0..candidates.len());
+        let question = serde_json::from_value::<RawQuestion>(candidates[index].clone())
+            .ok()
+            .map(|question| DailyQuestion::from_raw(question, None, &client.base_url));
+        match client.site {
+            Site::Com => question,
+            Site::Cn => question.map(DailyQuestion::with_percent_ac_rate),
+        }
+    };
+    if let Some(question) = &question {
+        info!(title = %question.title, "Random question found.");
+    }
+    Ok(question)
+}
+
+// A company that asked the problem in interviews
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CompanyTag {
+    name: String,
+    #[serde(default)]
+    times_encountered: u64,
+}
+
+// Company names from LeetCode's company tag stats, a JSON document in a string, most frequent first
+fn parse_company_tags(stats: &str) -> Result<Vec<String>, serde_json::Error> {
+    // Stats are grouped by time period, a company can appear in several of them
+    let periods: HashMap<String, Vec<CompanyTag>> = serde_json::from_str(stats)?;
+    let mut totals: HashMap<String, u64> = HashMap::new();
+    for tag in periods.into_values().flatten() {
+        *totals.entry(tag.name).or_default() += tag.times_encountered;
+    }
+    let mut companies: Vec<(String, u64)> = totals.into_iter().collect();
+    companies.sort_by(|(a_name, a_count), (b_name, b_count)| b_count.cmp(a_count).then_with(|| a_name.cmp(b_name)));
+    Ok(companies.into_iter().map(|(name, _)| name).collect())
+}
+
+// Single problem query, the same on both sites
+const QUESTION_QUERY: &str = "query questionData($titleSlug: String!) {question(titleSlug: $titleSlug) {title titleSlug difficulty acRate stats topicTags {name slug} paidOnly: isPaidOnly hints}}";
+
+// Fetch one problem by its title slug, `None` if there's no such problem
+pub async fn fetch_question_by_slug(client: &LeetCodeClient, slug: &str) -> Result<Option<DailyQuestion>, BotError> {
+    #[derive(Deserialize)]
+    struct Data {
+        question: Option<RawQuestion>,
+    }
+
+    let query = json!({
+        "query": QUESTION_QUERY,
+        "variables": {"titleSlug": slug},
+        "operationName": "questionData"
+    });
+    debug!(slug, "Sending request to LeetCode for a problem...");
+    let response = post_leetcode_graphql(client, &query).await?;
+    let question = parse_data::<Data>(response)?
+        .question
+        .map(|question| DailyQuestion::from_raw(question, None, &client.base_url));
+    Ok(match client.site {
+        Site::Com => question,
+        Site::Cn => question.map(DailyQuestion::with_percent_ac_rate),
+    })
+}
+
+// Problem search by keyword, returning each match's number and slug
+const SEARCH_QUERY_COM: &str = "query problemsetQuestionList($filters: QuestionListFilterInput) {problemsetQuestionList: questionList(categorySlug: \"\", limit: 50, skip: 0, filters: $filters) {questions: data {frontendQuestionId: questionFrontendId titleSlug}}}";
+const SEARCH_QUERY_CN: &str = "query problemsetQuestionList($filters: QuestionListFilterInput) {problemsetQuestionList(categorySlug: \"\", limit: 50, skip: 0, filters: $filters) {questions {frontendQuestionId titleSlug}}}";
+
+// Fetch one problem by the number shown on the site, `None` if there's no such problem
+pub async fn fetch_question_by_number(client: &LeetCodeClient, number: u32) -> Result<Option<DailyQuestion>, BotError> {
+    #[derive(Deserialize)]
+    struct Data {
+        #[serde(rename = "problemsetQuestionList")]
+        list: Option<List>,
+    }
+    #[derive(Deserialize)]
+    struct List {
+        questions: Vec<Match>,
+    }
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Match {
+        frontend_question_id: String,
+        title_slug: String,
+    }
+
+    let query = json!({
+        "query": match client.site {
+            Site::Com => SEARCH_QUERY_COM,
+            Site::Cn => SEARCH_QUERY_CN,
+        },
+        "variables": {"filters": {"searchKeywords": number.to_string()}},
+        "operationName": "problemsetQuestionList"
+    });
+    debug!(number, "Sending request to LeetCode to find a problem by number...");
+    let response = post_leetcode_graphql(client, &query).await?;
+    // The search also matches titles containing the number, so the exact match has to be picked out
+    let slug = parse_data::<Data>(response)?
+        .list
+        .map(|list| list.questions)
+        .unwrap_or_default()
+        .into_iter()
+        .find(|question| question.frontend_question_id == number.to_string())
+        .map(|question| question.title_slug);
+    match slug {
+        Some(slug) => fetch_question_by_slug(client, &slug).await,
+        None => Ok(None),
+    }
+}
+
+// Public solve counts of a LeetCode user
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    pub username: String,
+    pub easy: u32,
+    pub medium: u32,
+    pub hard: u32,
+}
+
+impl Profile {
+    pub fn total(&self) -> u32 {
+        self.easy + self.medium + self.hard
+    }
+}
+
+// Solved counts by difficulty; leetcode.cn only has them under the user's question progress
+const PROFILE_QUERY_COM: &str = "query userProfile($username: String!) {matchedUser(username: $username) {username submitStatsGlobal {acSubmissionNum {difficulty count}}}}";
+const PROFILE_QUERY_CN: &str = "query userQuestionProgress($username: String!) {progress: userProfileUserQuestionProgress(userSlug: $username) {numAcceptedQuestions {difficulty count}}}";
+
+// Fetch a user's public solve counts, `None` if there's no such user
+pub async fn fetch_profile(client: &LeetCodeClient, username: &str) -> Result<Option<Profile>, BotError> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct DataCom {
+        matched_user: Option<MatchedUser>,
+    }
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct MatchedUser {
+        username: String,
+        submit_stats_global: Stats,
+    }
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Stats {
+        ac_submission_num: Vec<Count>,
+    }
+    #[derive(Deserialize)]
+    struct DataCn {
+        progress: Option<Progress>,
+    }
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Progress {
+        num_accepted_questions: Vec<Count>,
+    }
+    #[derive(Deserialize)]
+    struct Count {
+        difficulty: String,
+        count: u32,
+    }
+
+    let query = json!({
+        "query": match client.site {
+            Site::Com => PROFILE_QUERY_COM,
+            Site::Cn => PROFILE_QUERY_CN,
+        },
+        "variables": {"username": username},
+    });
+    debug!(username, "Sending request to LeetCode for a user profile...");
+    let response = post_leetcode_graphql(client, &query).await?;
+    let (username, counts) = match client.site {
+        Site::Com => match parse_data::<DataCom>(response)?.matched_user {
+            Some(user) => (user.username, user.submit_stats_global.ac_submission_num),
+            None => return Ok(None),
+        },
+        Site::Cn => match parse_data::<DataCn>(response)?.progress {
+            Some(progress) => (username.to_string(), progress.num_accepted_questions),
+            None => return Ok(None),
+        },
+    };
+    // The counts also include an "All" entry, which the sum of the others replaces
+    let count = |difficulty: Difficulty| {
+        counts
+            .iter()
+            .filter(|count| Difficulty::parse(&count.difficulty) == Some(difficulty))
+            .map(|count| count.count)
+            .sum()
+    };
+    Ok(Some(Profile {
+        easy: count(Difficulty::Easy),
+        medium: count(Difficulty::Medium),
+        hard: count(Difficulty::Hard),
+        username,
+    }))
+}
+
+// A scheduled LeetCode contest
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contest {
+    pub title: String,
+    pub link: String,
+    pub start_time: DateTime<Utc>,
+    pub duration: Duration,
+}
+
+// Upcoming contest queries; leetcode.cn names the field contestUpcomingContests
+const CONTEST_QUERY_COM: &str = "query upcomingContests {upcomingContests {title titleSlug startTime duration}}";
+const CONTEST_QUERY_CN: &str = "query contestUpcomingContests {upcomingContests: contestUpcomingContests {title titleSlug startTime duration}}";
+
+// Fetch the next contest starting after `now`, `None` if none is scheduled
+pub async fn fetch_upcoming_contest(
+    client: &LeetCodeClient,
+    now: DateTime<Utc>,
+) -> Result<Option<Contest>, BotError> {
+    let query = json!({
+        "query": match client.site {
+            Site::Com => CONTEST_QUERY_COM,
+            Site::Cn => CONTEST_QUERY_CN,
+        },
+        "variables": {},
+    });
+    debug!(site = ?client.site, "Sending request to LeetCode for upcoming contests...");
+    let response = post_leetcode_graphql(client, &query).await?;
+
+    let contests = response
+        .get("data")
+        .and_then(|data| data.get("upcomingContests"))
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    let contest = contests
+        .iter()
+        .filter_map(|contest| {
+            let title = contest.get("title")?.as_str()?;
+            let slug = contest.get("titleSlug")?.as_str()?;
+            let start_time = DateTime::from_timestamp(contest.get("startTime")?.as_i64()?, 0)?;
+            Some(Contest {
+                title: title.to_string(),
+                link: format!("{}/contest/{}/", client.base_url, slug),
+                start_time,
+                duration: Duration::from_secs(contest.get("duration").and_then(Value::as_u64).unwrap_or(0)),
+            })
+        })
+        .filter(|contest| contest.start_time > now)
+        .min_by_key(|contest| contest.start_time);
+    if let Some(contest) = &contest {
+        info!(title = %contest.title, "Upcoming contest found.");
+    }
+    Ok(contest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_string_contains, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn client_for(response: ResponseTemplate) -> (MockServer, LeetCodeClient) {
+        let server = MockServer::start().await;
+        Mock::given(method("POST")).and(path("/graphql/")).respond_with(response).mount(&server).await;
+        let client = build_leetcode_client(&server.uri(), None, None, Duration::from_secs(5)).unwrap();
+        (server, client)
+    }
+
+    #[tokio::test]
+    async fn parses_the_daily_question() {
+        let body = json!({"data": {"activeDailyCodingChallengeQuestion": {
+            "date": "2024-03-10",
+            "link": "/problems/two-sum/",
+            "question": {
+                "title": "Two Sum",
+                "titleSlug": "two-sum",
+                "difficulty": "Easy",
+                "acRate": 52.5,
+                "stats": "{\"totalSubmission\": \"25.1M\"}",
+                "topicTags": [{"name": "Array", "slug": "array"}, {"name": "Hash Table", "slug": "hash-table"}],
+                "paidOnly": false,
+                "hints": ["Try a hash map.", "Look up the complement."]
+            }
+        }}});
+        let (server, client) = client_for(ResponseTemplate::new(200).set_body_json(body)).await;
+
+        let question = fetch_leetcode_daily_question(&client).await.unwrap().unwrap();
+        assert_eq!(question.title, "Two Sum");
+        assert_eq!(question.slug, "two-sum");
+        assert_eq!(question.link, format!("{}/problems/two-sum/", server.uri()));
+        assert_eq!(question.difficulty, Some(Difficulty::Easy));
+        assert_eq!(question.ac_rate, Some(52.5));
+        assert_eq!(question.total_submissions.as_deref(), Some("25.1M"));
+        assert_eq!(question.tags.iter().map(|tag| tag.slug.as_str()).collect::<Vec<_>>(), ["array", "hash-table"]);
+        assert!(!question.paid_only);
+        assert_eq!(question.hints, 2);
+    }
+
+    #[tokio::test]
+    async fn missing_data_field_is_a_schema_error() {
+        let body = json!({"errors": [{"message": "Cannot query field \"activeDailyCodingChallengeQuestion\""}]});
+        let (_server, client) = client_for(ResponseTemplate::new(200).set_body_json(body)).await;
+
+        let err = fetch_leetcode_daily_question(&client).await.unwrap_err();
+        assert!(matches!(&err, BotError::Parse(problem) if problem.contains("Cannot query field")), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn renamed_question_field_is_a_schema_error() {
+        let body = json!({"data": {"activeDailyCodingChallengeQuestion": {
+            "link": "/problems/two-sum/",
+            "problem": {"title": "Two Sum", "titleSlug": "two-sum"}
+        }}});
+        let (_server, client) = client_for(ResponseTemplate::new(200).set_body_json(body)).await;
+
+        assert!(matches!(fetch_leetcode_daily_question(&client).await, Err(BotError::Parse(_))));
+    }
+
+    #[tokio::test]
+    async fn no_daily_today_is_not_an_error() {
+        let body = json!({"data": {"activeDailyCodingChallengeQuestion": null}});
+        let (_server, client) = client_for(ResponseTemplate::new(200).set_body_json(body)).await;
+
+        assert!(fetch_leetcode_daily_question(&client).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn link_that_is_not_a_string_falls_back_to_the_slug() {
+        let body = json!({"data": {"activeDailyCodingChallengeQuestion": {
+            "link": 42,
+            "question": {"title": "Add Two Numbers", "titleSlug": "add-two-numbers", "difficulty": "Medium"}
+        }}});
+        let (server, client) = client_for(ResponseTemplate::new(200).set_body_json(body)).await;
+
+        let question = fetch_leetcode_daily_question(&client).await.unwrap().unwrap();
+        assert_eq!(question.link, format!("{}/problems/add-two-numbers/", server.uri()));
+        assert_eq!(question.difficulty, Some(Difficulty::Medium));
+        assert_eq!(question.ac_rate, None);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_returned_without_retrying() {
+        let (server, client) = client_for(ResponseTemplate::new(403)).await;
+
+        assert!(fetch_leetcode_daily_question(&client).await.is_err());
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn invalid_json_is_an_error() {
+        let (_server, client) = client_for(ResponseTemplate::new(200).set_body_string("<html>blocked</html>")).await;
+
+        assert!(matches!(fetch_leetcode_daily_question(&client).await, Err(BotError::Http(_))));
+    }
+
+    #[tokio::test]
+    async fn picks_the_next_contest_that_has_not_started() {
+        let body = json!({"data": {"upcomingContests": [
+            {"title": "Biweekly Contest 126", "titleSlug": "biweekly-contest-126", "startTime": 1710599400, "duration": 5400},
+            {"title": "Weekly Contest 388", "titleSlug": "weekly-contest-388", "startTime": 1710037800, "duration": 5400},
+            {"title": "Weekly Contest 387", "titleSlug": "weekly-contest-387", "startTime": 1709433000, "duration": 5400}
+        ]}});
+        let (server, client) = client_for(ResponseTemplate::new(200).set_body_json(body)).await;
+        let now = DateTime::from_timestamp(1709500000, 0).unwrap();
+
+        let contest = fetch_upcoming_contest(&client, now).await.unwrap().unwrap();
+        assert_eq!(contest.title, "Weekly Contest 388");
+        assert_eq!(contest.link, format!("{}/contest/weekly-contest-388/", server.uri()));
+        assert_eq!(contest.start_time, DateTime::from_timestamp(1710037800, 0).unwrap());
+        assert_eq!(contest.duration, Duration::from_secs(5400));
+    }
+
+    #[tokio::test]
+    async fn no_scheduled_contest_means_none() {
+        let body = json!({"data": {"upcomingContests": []}});
+        let (_server, client) = client_for(ResponseTemplate::new(200).set_body_json(body)).await;
+        assert_eq!(fetch_upcoming_contest(&client, Utc::now()).await.unwrap(), None);
+    }
+
+    #[test]
+    fn company_tags_are_merged_across_periods() {
+        let stats = json!({
+            "1": [{"name": "Google", "slug": "google", "timesEncountered": 3}, {"name": "Amazon", "slug": "amazon", "timesEncountered": 2}],
+            "2": [{"name": "Amazon", "slug": "amazon", "timesEncountered": 4}, {"name": "Apple", "slug": "apple", "timesEncountered": 1}]
+        });
+
+        assert_eq!(parse_company_tags(&stats.to_string()).unwrap(), ["Amazon", "Google", "Apple"]);
+    }
+
+    #[tokio::test]
+    async fn authenticated_daily_sends_credentials_and_premium_fields() {
+        let server = MockServer::start().await;
+        let stats = json!({"1": [{"name": "Google", "timesEncountered": 3}]});
+        let body = json!({"data": {"activeDailyCodingChallengeQuestion": {
+            "link": "/problems/two-sum/",
+            "question": {"title": "Two Sum", "titleSlug": "two-sum", "companyTagStats": stats.to_string()}
+        }}});
+        Mock::given(method("POST"))
+            .and(path("/graphql/"))
+            .and(header("cookie", "LEETCODE_SESSION=session; csrftoken=token"))
+            .and(header("x-csrftoken", "token"))
+            .and(body_string_contains("companyTagStats"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(&server)
+            .await;
+        let credentials = Credentials {
+            session: "session".to_string(),
+            csrf: Some("token".to_string()),
+        };
+        let client = build_leetcode_client(&server.uri(), None, Some(&credentials), Duration::from_secs(5)).unwrap();
+
+        let question = fetch_leetcode_daily_question(&client).await.unwrap().unwrap();
+        assert_eq!(question.companies, ["Google"]);
+    }
+
+    #[tokio::test]
+    async fn rejected_premium_fields_fall_back_to_the_free_query() {
+        let server = MockServer::start().await;
+        let rejected = json!({"errors": [{"message": "Cannot query field \"companyTagStats\""}]});
+        Mock::given(method("POST"))
+            .and(body_string_contains("companyTagStats"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(rejected))
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        let body = json!({"data": {"activeDailyCodingChallengeQuestion": {
+            "link": "/problems/two-sum/",
+            "question": {"title": "Two Sum", "titleSlug": "two-sum"}
+        }}});
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(200).set_body_json(body)).mount(&server).await;
+        let credentials = Credentials {
+            session: "expired".to_string(),
+            csrf: None,
+        };
+        let client = build_leetcode_client(&server.uri(), None, Some(&credentials), Duration::from_secs(5)).unwrap();
+
+        let question = fetch_leetcode_daily_question(&client).await.unwrap().unwrap();
+        assert_eq!(question.title, "Two Sum");
+        assert!(question.companies.is_empty());
+    }
+
+    #[test]
+    fn credentials_are_redacted_in_debug_output() {
+        let credentials = Credentials {
+            session: "secret-session".to_string(),
+            csrf: Some("secret-token".to_string()),
+        };
+        let debug = format!("{:?}", credentials);
+        assert!(!debug.contains("secret"), "{}", debug);
+    }
+
+    #[tokio::test]
+    async fn fetches_a_premium_problem_by_slug() {
+        let body = json!({"data": {"question": {
+            "title": "Meeting Rooms",
+            "titleSlug": "meeting-rooms",
+            "difficulty": "Easy",
+            "paidOnly": true
+        }}});
+        let (server, client) = client_for(ResponseTemplate::new(200).set_body_json(body)).await;
+
+        let question = fetch_question_by_slug(&client, "meeting-rooms").await.unwrap().unwrap();
+        assert_eq!(question.title, "Meeting Rooms");
+        assert_eq!(question.link, format!("{}/problems/meeting-rooms/", server.uri()));
+        assert!(question.paid_only);
+    }
+
+    #[tokio::test]
+    async fn unknown_slug_is_not_found() {
+        let body = json!({"data": {"question": null}});
+        let (_server, client) = client_for(ResponseTemplate::new(200).set_body_json(body)).await;
+
+        assert!(fetch_question_by_slug(&client, "no-such-problem").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn number_is_resolved_to_the_exact_match() {
+        let server = MockServer::start().await;
+        let search = json!({"data": {"problemsetQuestionList": {"questions": [
+            {"frontendQuestionId": "10", "titleSlug": "regular-expression-matching"},
+            {"frontendQuestionId": "1", "titleSlug": "two-sum"}
+        ]}}});
+        Mock::given(method("POST"))
+            .and(body_string_contains("searchKeywords"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(search))
+            .mount(&server)
+            .await;
+        let question = json!({"data": {"question": {"title": "Two Sum", "titleSlug": "two-sum"}}});
+        Mock::given(method("POST"))
+            .and(body_string_contains("\"titleSlug\":\"two-sum\""))
+            .respond_with(ResponseTemplate::new(200).set_body_json(question))
+            .mount(&server)
+            .await;
+        let client = build_leetcode_client(&server.uri(), None, None, Duration::from_secs(5)).unwrap();
+
+        let question = fetch_question_by_number(&client, 1).await.unwrap().unwrap();
+        assert_eq!(question.title, "Two Sum");
+        assert!(fetch_question_by_number(&client, 2).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn profile_counts_solves_by_difficulty() {
+        let body = json!({"data": {"matchedUser": {"username": "alice", "submitStatsGlobal": {"acSubmissionNum": [
+            {"difficulty": "All", "count": 60},
+            {"difficulty": "Easy", "count": 30},
+            {"difficulty": "Medium", "count": 25},
+            {"difficulty": "Hard", "count": 5}
+        ]}}}});
+        let (_server, client) = client_for(ResponseTemplate::new(200).set_body_json(body)).await;
+
+        let profile = fetch_profile(&client, "Alice").await.unwrap().unwrap();
+        assert_eq!(profile, Profile { username: "alice".to_string(), easy: 30, medium: 25, hard: 5 });
+        assert_eq!(profile.total(), 60);
+    }
+
+    #[tokio::test]
+    async fn unknown_user_is_not_found() {
+        let body = json!({"data": {"matchedUser": null}, "errors": [{"message": "That user does not exist."}]});
+        let (_server, client) = client_for(ResponseTemplate::new(200).set_body_json(body)).await;
+
+        assert!(fetch_profile(&client, "nobody").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn fetches_the_problem_index() {
+        let body = json!({"data": {"problemsetQuestionList": {"total": 2, "questions": [
+            {"title": "Two Sum", "titleSlug": "two-sum", "difficulty": "Easy", "paidOnly": false, "topicTags": [{"name": "Array", "slug": "array"}]},
+            {"title": "Paid One", "titleSlug": "paid-one", "difficulty": "Hard", "paidOnly": true, "topicTags": []}
+        ]}}});
+        let (_server, client) = client_for(ResponseTemplate::new(200).set_body_json(body)).await;
+
+        let problems = fetch_problem_index(&client).await.unwrap();
+        assert_eq!(problems.len(), 2);
+        assert_eq!(problems[0].slug, "two-sum");
+        assert_eq!(problems[0].tags, ["array"]);
+        assert_eq!(problems[1].difficulty, Some(Difficulty::Hard));
+        assert!(problems[1].paid_only);
+    }
+}
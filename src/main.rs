@@ -1,237 +1,4014 @@
-use chrono::{NaiveTime, Local};
+use chrono::{DateTime, Datelike, Local, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use async_trait::async_trait;
+use chrono_tz::Tz;
+use leetcode::{
+    build_leetcode_client, code_lang_slug, code_lang_slugs, fetch_leetcode_daily_question, fetch_profile, fetch_question_by_number, fetch_question_by_slug, fetch_random_question, fetch_upcoming_contest, tag_slug,
+    Contest, DailyQuestion, Difficulty, LeetCodeClient, ProblemFilter, Profile, TopicTag,
+};
+use error::BotError;
+use notifier::{Challenge, Notifier, Trigger};
+use problem_index::ProblemIndex;
+use rand::seq::SliceRandom;
 use rand::Rng;
-use reqwest::Client;
-use serde_json::Value;
-use std::collections::{HashMap, HashSet};
-use std::fs;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use teloxide::net::Download;
 use teloxide::prelude::*;
-use teloxide::types::{ChatId, ParseMode};
+use teloxide::payloads::SendMessage;
+use teloxide::requests::{JsonRequest, Output};
+use teloxide::types::{
+    BotCommand, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, MessageId, MessageKind, ParseMode, UserId,
+};
+use teloxide::{ApiError, RequestError};
+use teloxide::update_listeners::webhooks;
+use teloxide::utils::html;
 use teloxide::Bot;
-use tokio::sync::Mutex;
-use tokio::time::{interval_at, sleep, Duration, Instant};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::JoinSet;
+use tokio::time::{interval, sleep, Duration, Instant};
+use tokio_util::sync::CancellationToken;
 use dotenv::dotenv;
-use std::env;
-use tokio::fs as async_fs;
-use tokio::io::AsyncWriteExt;
+use config::{BotMode, Config};
+use health::Health;
+use i18n::{Key, Lang};
+use metrics::METRICS;
+use rate_limit::RateLimiter;
+use storage::{FeaturedProblem, FileStorage, Routes, SendOutcome, SendRecord, SqliteStorage, Storage};
+use subscriptions::{ChatSettings, ImportMode, SubscribeResult, SubscriptionDump, Subscriptions};
+use tracing::{debug, error, info, instrument, warn, Instrument};
+use tracing_subscriber::EnvFilter;
 
-// Fetch the daily LeetCode question
-async fn fetch_leetcode_daily_question(client: &Client) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
-    let query = r#"
-    {
-        "query": "query questionOfToday {activeDailyCodingChallengeQuestion {date link question {difficulty}}}",
-        "variables": {},
-        "operationName": "questionOfToday"
+mod config;
+mod error;
+mod health;
+mod i18n;
+mod leetcode;
+mod metrics;
+mod notifier;
+mod problem_index;
+mod rate_limit;
+mod storage;
+mod subscriptions;
+mod template;
+
+// Supported commands with their arguments and descriptions, used for /help and the Telegram command menu
+const COMMANDS: &[(&str, &str, &str)] = &[
+    ("start", "", "Subscribe this chat to the daily challenge"),
+    ("stop", "", "Unsubscribe this chat"),
+    ("today", "", "Show today's problem right now"),
+    ("resend", "", "Send today's challenge again, like the scheduled message"),
+    ("random", "[easy|medium|hard]", "Show a random free problem"),
+    ("problem", "<slug|number>", "Show a specific problem, e.g. two-sum or 1"),
+    ("contest", "", "Show the next LeetCode contest"),
+    ("timezone", "<tz>", "Set the timezone, e.g. Europe/Berlin"),
+    ("settime", "<HH:MM>", "Set the local delivery time"),
+    ("days", "<mon,tue,...|weekdays|all>", "Only receive the daily on these weekdays"),
+    ("difficulty", "<easy,medium,hard|all>", "Only receive problems of these difficulties"),
+    ("pin", "<on|off> | notify <on|off>", "Pin the daily challenge message, optionally notifying members"),
+    ("autounpin", "<on|off> | delete <on|off>", "Unpin the previous challenge, optionally deleting it"),
+    ("thread", "<on|off>", "Post each daily as a reply to one pinned message"),
+    ("tags", "<graph,dp,...|all>", "Only receive problems with one of these topic tags"),
+    ("premium", "<skip|show>", "Skip premium problems or show them"),
+    ("companies", "<on|off>", "List the companies that asked the daily, needs premium credentials"),
+    ("breakdown", "<on|off>", "Say how the daily's difficulty compares to this month's, e.g. 3rd Hard"),
+    ("hints", "<on|off>", "Say how many hints the daily has, without revealing them"),
+    ("bonus", "<on|off|easy|medium|hard>", "Also get a random bonus problem with the daily"),
+    ("preview", "<on|off|link on|link off>", "Include the start of the problem statement, or show the link preview"),
+    ("prefslang", "<language|off>", "Include starter code in this language, e.g. python"),
+    ("template", "<text|off>", "Use your own message, with {title}, {link} and {difficulty}"),
+    ("lang", "<en|es>", "Set the language of the bot's messages"),
+    ("weekly", "<on|off>", "Get a weekly recap of the past week's problems"),
+    ("snooze", "<days|off>", "Pause daily challenges for a number of days"),
+    ("solved", "", "Mark today's problem as solved"),
+    ("streak", "", "Show your solving streak"),
+    ("profile", "[username]", "Show a LeetCode user's solved problems, yours if you used /setuser"),
+    ("setuser", "<username|off>", "Link your LeetCode username in this chat"),
+    ("leaderboard", "", "Rank the members who used /setuser by problems solved"),
+    ("reminder", "<on|off>", "Get a reminder when nobody solved the daily a few hours after it arrived"),
+    ("next", "", "Show when the next daily challenge arrives"),
+    ("status", "", "Show this chat's subscription and settings"),
+    ("count", "", "Show how many chats get the daily challenge"),
+    ("feedback", "<text>", "Send a message to the bot's admin"),
+    ("version", "", "Show which build of the bot is running"),
+    ("help", "", "Show this list of commands"),
+];
+
+// Build the /help reply from the command list
+fn help_text() -> String {
+    let mut text = "<b>Available commands</b>\n".to_string();
+    for (command, arguments, description) in COMMANDS {
+        let usage = if arguments.is_empty() {
+            format!("/{}", command)
+        } else {
+            format!("/{} {}", command, arguments)
+        };
+        text.push_str(&format!("\n{} - {}", html::escape(&usage), description));
     }
-    "#;
-    println!("Sending request to LeetCode for daily question...");
-    let response = client
-        .post("https://leetcode.com/graphql/")
-        .header("Content-type", "application/json")
-        .header("Origin", "leetcode.com")
-        .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/58.0.3029.110 Safari/537.3")
-        .body(query)
-        .send()
-        .await?
-        .json::<HashMap<String, Value>>()
-        .await?;
+    text
+}
 
-    println!("Response from LeetCode arrived for daily question.");
-    if let Some(data) = response.get("data") {
-        if let Some(active_daily_coding_challenge_question) = data.get("activeDailyCodingChallengeQuestion") {
-            if let Some(link) = active_daily_coding_challenge_question.get("link") {
-                if let Some(link_str) = link.as_str() {
-                    println!("Daily question found.");
-                    return Ok(Some(format!("https://leetcode.com{}", link_str)));
-                }
-            }
+// End a daily message with the configured footer, which is HTML written by the operator and not escaped
+fn with_footer(text: String, footer: Option<&str>) -> String {
+    match footer {
+        Some(footer) => format!("{}\n\n{}", text, footer),
+        None => text,
+    }
+}
+
+// Build the message announcing the daily question in the chat's language, labelled e.g. "Daily"
+fn format_daily_message(lang: Lang, label: Key, daily_question: Option<&DailyQuestion>) -> String {
+    format!(
+        "{}\n\n{}: {}",
+        i18n::text(lang, Key::DailyHeader),
+        i18n::text(lang, label),
+        daily_question.map_or_else(|| i18n::text(lang, Key::NotAvailable).to_string(), format_question),
+    )
+}
+
+// Describe a question with its difficulty, link and statistics as HTML, shared by every message that shows a problem
+fn format_question(question: &DailyQuestion) -> String {
+    let premium = if question.paid_only { " (Premium)" } else { "" };
+    let title = format!("<b>{}</b>", html::escape(&question.title));
+    let link = html::escape(&question.link);
+    let mut text = match question.difficulty {
+        Some(difficulty) => format!("{} {}{} ({})\n{}", difficulty.emoji(), title, premium, difficulty, link),
+        None => format!("{}{}\n{}", title, premium, link),
+    };
+    if let Some(ac_rate) = question.ac_rate {
+        text.push_str(&format!("\nAcceptance: {:.1}%", ac_rate));
+    }
+    if let Some(total_submissions) = &question.total_submissions {
+        text.push_str(&format!("\nSubmissions: {}", html::escape(total_submissions)));
+    }
+    if !question.tags.is_empty() {
+        text.push_str(&format!("\nTopics: {}", format_topic_tags(&question.tags)));
+    }
+    text
+}
+
+// Turn a problem as typed by a user, e.g. "Two Sum" or a problem link, into its title slug
+fn problem_slug(problem: &str) -> String {
+    let problem = problem.trim().trim_end_matches('/');
+    let problem = problem.rsplit_once("/problems/").map_or(problem, |(_, slug)| slug);
+    problem.to_lowercase().split_whitespace().collect::<Vec<_>>().join("-")
+}
+
+// Shorten text to at most `max_chars` characters, ending with an ellipsis when cut
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        None => text.to_string(),
+        Some(_) => {
+            let cut: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+            format!("{}…", cut.trim_end())
         }
     }
+}
 
-    Ok(None)
+// Start of the problem statement for chats with /preview on
+fn format_preview(question: &DailyQuestion, max_chars: usize) -> Option<String> {
+    let content = question.content.as_deref()?;
+    Some(format!("\n\n<i>{}</i>", html::escape(&truncate_chars(content, max_chars))))
 }
 
+// Longest starter code included in a message, longer snippets only get the link
+const MAX_SNIPPET_CHARS: usize = 1500;
 
-// Send the LeetCode challenges to all subscribed chats
-async fn send_daily_challenge(bot: Bot, chat_ids: Arc<Mutex<HashSet<ChatId>>>, client: Client) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let daily_question = fetch_leetcode_daily_question(&client).await?;
+// Starter code block for the chat's preferred language, `None` if the problem doesn't have one
+fn format_snippet(question: &DailyQuestion, code_lang: Option<&str>) -> Option<String> {
+    let code_lang = code_lang?;
+    let snippet = question.code_snippets.iter().find(|snippet| snippet.lang_slug == code_lang)?;
+    if snippet.code.chars().count() > MAX_SNIPPET_CHARS {
+        return Some(format!("\n\nSolve in {}: {}", html::escape(&snippet.lang), html::escape(&question.link)));
+    }
+    Some(format!(
+        "\n\nSolve in {}:\n<pre><code class=\"language-{}\">{}</code></pre>",
+        html::escape(&snippet.lang),
+        html::escape(&snippet.lang_slug),
+        html::escape(&snippet.code)
+    ))
+}
 
-    let message_text = format!(
-        "Today's LeetCode Challenge:\n\nDaily: {}",
-        daily_question.unwrap_or_else(|| "Not available".to_string()),
+// Most topic tags listed in a message, the rest are summarized as "+N more"
+const MAX_TAGS_SHOWN: usize = 4;
 
-    );
-    println!("Sending message to all chats...");
-    let chat_ids_guard = chat_ids.lock().await;
-    for &chat_id in chat_ids_guard.iter() {
-        let delay = rand::thread_rng().gen_range(0..600); // Random delay up to 10 minutes
-        println!("Sending message to chat {} with a delay of {} seconds...", chat_id, delay);
-        sleep(Duration::from_secs(delay)).await;
-        let message = bot.send_message(chat_id, message_text.clone())
-            .parse_mode(ParseMode::Html)
-            .disable_web_page_preview(true)
-            .send()
-            .await?;
-        bot.pin_chat_message(chat_id, message.id)
-            .disable_notification(true)
-            .send()
-            .await?;
-        println!("Message sent to chat {}.", chat_id);
+// List topic tags like "Array, Two Pointers, +2 more"
+fn format_topic_tags(tags: &[TopicTag]) -> String {
+    let mut shown: Vec<String> = tags.iter().take(MAX_TAGS_SHOWN).map(|tag| html::escape(&tag.name)).collect();
+    if tags.len() > MAX_TAGS_SHOWN {
+        shown.push(format!("+{} more", tags.len() - MAX_TAGS_SHOWN));
+    }
+    shown.join(", ")
+}
+
+// Build the /contest reply with the start time in the chat's timezone
+fn format_contest(contest: &Contest, now: DateTime<Utc>, timezone: Option<Tz>) -> String {
+    let starts_in = contest.start_time - now;
+    format!(
+        "<b>Next LeetCode contest:</b>\n<a href=\"{}\">{}</a>\nStarts: {} {}, in {}\nDuration: {}",
+        html::escape(&contest.link),
+        html::escape(&contest.title),
+        local_now(contest.start_time, timezone).format("%a %Y-%m-%d %H:%M"),
+        timezone.map_or_else(|| "(server time)".to_string(), |tz| format!("({})", tz)),
+        format_duration(starts_in),
+        format_duration(chrono::Duration::from_std(contest.duration).unwrap_or_default()),
+    )
+}
+
+// List the template placeholders as {title}, {link} and {difficulty}
+fn format_placeholders() -> String {
+    let placeholders: Vec<String> = template::PLACEHOLDERS.iter().map(|name| format!("{{{}}}", name)).collect();
+    match placeholders.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+        None => String::new(),
     }
+}
 
-    Ok(())
+// Build the weekly recap from the stored daily questions, oldest first
+fn format_weekly_digest(dailies: &[(NaiveDate, DailyQuestion)]) -> String {
+    if dailies.is_empty() {
+        return "<b>Weekly LeetCode Recap</b>\n\nNo daily problems were recorded this week.".to_string();
+    }
+    let mut text = format!("<b>Weekly LeetCode Recap</b>\n\nThe last {} daily {}:", dailies.len(), problems(dailies.len()));
+    for (date, question) in dailies {
+        text.push_str(&format!("\n\n{}: {}", date.format("%a %b %-d"), format_question(question)));
+    }
+    text
 }
 
-// Calculate the duration until the next trigger time
-fn duration_until_next_trigger(trigger_time: NaiveTime) -> Duration {
-    let now = Local::now().naive_local();
-    let target_datetime = now.date().and_time(trigger_time);
+// "problem" or "problems" depending on the count
+fn problems(count: usize) -> &'static str {
+    if count == 1 {
+        "problem"
+    } else {
+        "problems"
+    }
+}
+
+// Build the admin /stats reply from the shared counters
+async fn format_stats(app: &App) -> String {
+    let now = Utc::now();
+    let uptime = now - app.started_at;
+    let last_run = match *app.last_run.lock().await {
+        Some((at, summary)) => format!("{} ({} ago)", summary, format_duration(now - at)),
+        None => "none yet".to_string(),
+    };
+    let fetches = METRICS.fetch_latency.get_sample_count();
+    let average_latency = if fetches == 0 {
+        "no fetches yet".to_string()
+    } else {
+        format!("{:.0}ms over {} fetches", METRICS.fetch_latency.get_sample_sum() / fetches as f64 * 1000.0, fetches)
+    };
+    format!(
+        "<b>Stats</b>\nSubscribers: {}\nWaitlist: {}\nLast run: {}\nSends since start: {} succeeded, {} failed\nAverage fetch latency: {}\nUptime: {}",
+        app.subscriptions.len().await,
+        app.subscriptions.waitlist_len().await,
+        last_run,
+        METRICS.sends_succeeded.get(),
+        METRICS.sends_failed.get(),
+        average_latency,
+        format_duration(uptime),
+    )
+}
 
-    let next_trigger = if now.time() < trigger_time {
-        target_datetime
+// Build the /status reply for a chat
+fn format_status(settings: Option<&ChatSettings>, default_trigger_time: NaiveTime, send_jitter: Duration) -> String {
+    let Some(settings) = settings else {
+        return "<b>Status</b>\nSubscribed: no. Send /start to subscribe.".to_string();
+    };
+    let trigger_time = settings.trigger_time(default_trigger_time);
+    let spacing = if send_jitter.is_zero() {
+        "Sends to chats are not staggered".to_string()
     } else {
-        target_datetime + chrono::Duration::days(1)
+        format!("Sends to chats are spread over up to {}s", send_jitter.as_secs())
     };
+    format!(
+        "<b>Status</b>\nSubscribed: yes\nDelivery time: {}{}\nTimezone: {}\nDays: {}\nDifficulties: {}\nTags: {}\nPremium problems: {}\nBonus problem: {}\nCompany tags: {}\nDifficulty breakdown: {}\nHint count: {}\nPreview: {}\nLink preview: {}\nStarter code: {}\nTemplate: {}\nLanguage: {}\nPinning: {}\nPin notifications: {}\nAuto-unpin: {}\nReply thread: {}\nWeekly recap: {}\nSolve reminder: {}\nSnoozed: {}\nNext challenge in {}\n{}",
+        trigger_time.format("%H:%M"),
+        if settings.trigger_time.is_none() { " (default)" } else { "" },
+        settings.timezone.map_or_else(|| "server default".to_string(), |tz| tz.to_string()),
+        format_weekdays(&settings.days),
+        format_difficulties(&settings.difficulties),
+        format_tags(&settings.tags),
+        if settings.show_premium { "shown" } else { "skipped" },
+        format_bonus(settings),
+        format_toggle(settings.companies),
+        format_toggle(settings.breakdown),
+        format_toggle(settings.hints),
+        format_toggle(settings.preview),
+        format_toggle(settings.link_preview),
+        settings.code_lang.as_deref().unwrap_or("off"),
+        if settings.template.is_some() { "custom" } else { "default" },
+        settings.lang.code(),
+        format_toggle(!settings.pin_disabled),
+        format_toggle(settings.pin_notify),
+        match (settings.auto_unpin, settings.delete_previous) {
+            (false, _) => "off",
+            (true, false) => "on",
+            (true, true) => "on, deleting the previous message",
+        },
+        format_toggle(settings.reply_thread),
+        format_toggle(settings.weekly_digest),
+        format_toggle(settings.reminder),
+        match settings.snooze_days_left(local_now(Utc::now(), settings.timezone).date()) {
+            0 => "no".to_string(),
+            left => format!("{} more {}", left, days(left)),
+        },
+        format_duration(duration_until_next_trigger(Utc::now(), trigger_time, settings.timezone)),
+        spacing,
+    )
+}
+
+// Whether the text can be a LeetCode username, checked before it's sent to LeetCode
+fn valid_username(username: &str) -> bool {
+    (1..=50).contains(&username.len())
+        && username.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+// How long a fetched profile is reused
+const PROFILE_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+// A user's profile, from the cache if it was fetched recently; unknown users aren't cached so a new account shows up right away
+async fn cached_profile(app: &App, username: &str) -> Result<Option<Profile>, BotError> {
+    let key = username.to_ascii_lowercase();
+    if let Some((fetched_at, profile)) = app.profile_cache.lock().await.get(&key) {
+        if fetched_at.elapsed() < PROFILE_CACHE_TTL {
+            return Ok(Some(profile.clone()));
+        }
+    }
+    let profile = fetch_profile(&app.client, username).await?;
+    let mut cache = app.profile_cache.lock().await;
+    cache.retain(|_, (fetched_at, _)| fetched_at.elapsed() < PROFILE_CACHE_TTL);
+    if let Some(profile) = &profile {
+        cache.insert(key, (Instant::now(), profile.clone()));
+    }
+    Ok(profile)
+}
+
+// Reply to /leaderboard, most problems solved first
+fn format_leaderboard(mut profiles: Vec<Profile>, missing: &[String]) -> String {
+    profiles.sort_by(|a, b| b.total().cmp(&a.total()).then_with(|| b.hard.cmp(&a.hard)).then_with(|| a.username.cmp(&b.username)));
+    let mut text = "<b>Leaderboard</b>".to_string();
+    for (rank, profile) in profiles.iter().enumerate() {
+        text.push_str(&format!(
+            "\n{}. {} - {} solved ({}/{}/{})",
+            rank + 1,
+            html::escape(&profile.username),
+            profile.total(),
+            profile.easy,
+            profile.medium,
+            profile.hard
+        ));
+    }
+    if !missing.is_empty() {
+        text.push_str(&format!("\nCouldn't load: {}", html::escape(&missing.join(", "))));
+    }
+    text
+}
+
+// Reply to /profile
+fn format_profile(profile: &Profile) -> String {
+    format!(
+        "<b>{}</b> solved {} problems\n{} Easy: {}\n{} Medium: {}\n{} Hard: {}",
+        html::escape(&profile.username),
+        profile.total(),
+        Difficulty::Easy.emoji(),
+        profile.easy,
+        Difficulty::Medium.emoji(),
+        profile.medium,
+        Difficulty::Hard.emoji(),
+        profile.hard
+    )
+}
 
-    let duration = next_trigger - now;
-    println!("Duration until next trigger: {}", duration);
-    Duration::from_secs(duration.num_seconds() as u64)
+// "day" or "days" depending on the count
+fn days(count: u32) -> &'static str {
+    if count == 1 {
+        "day"
+    } else {
+        "days"
+    }
+}
+
+// Longest snooze accepted by /snooze
+const MAX_SNOOZE_DAYS: u32 = 365;
+
+// Parse an on/off argument
+fn parse_toggle(argument: &str) -> Option<bool> {
+    match argument.to_ascii_lowercase().as_str() {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
 }
 
-// Load chat IDs from the file
-async fn load_chat_ids(file_path: &str) -> HashSet<ChatId> {
-    println!("Loading chat IDs from file...");
-    if let Ok(data) = fs::read_to_string(file_path) {
-        println!("Chat IDs file found.");
-        serde_json::from_str(&data).unwrap_or_default()
+// Describe an on/off setting for replies
+fn format_toggle(enabled: bool) -> &'static str {
+    if enabled {
+        "on"
     } else {
-        println!("Chat IDs file not found, creating a new one.");
-        HashSet::new()
+        "off"
+    }
+}
+
+// Most companies listed in the daily
+const MAX_COMPANIES_SHOWN: usize = 5;
+
+// Companies line for the daily message, `None` when no companies are known
+fn format_companies(companies: &[String]) -> Option<String> {
+    if companies.is_empty() {
+        return None;
+    }
+    let shown: Vec<String> = companies.iter().take(MAX_COMPANIES_SHOWN).map(|name| html::escape(name)).collect();
+    let more = companies.len().saturating_sub(MAX_COMPANIES_SHOWN);
+    let more = if more > 0 { format!(" +{} more", more) } else { String::new() };
+    Some(format!("\nCompanies: {}{}", shown.join(", "), more))
+}
+
+// 1st, 2nd, 3rd, 4th, ..., 11th, 12th, 13th, ..., 21st
+fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+// Place the daily among this month's dailies of its difficulty, e.g. "3rd Hard this month". `history` holds the
+// recorded dailies since the first of the month; without the first there's a gap, so nothing is claimed.
+fn format_breakdown(date: NaiveDate, question: &DailyQuestion, history: &[(NaiveDate, DailyQuestion)]) -> Option<String> {
+    let difficulty = question.difficulty?;
+    let first_of_month = date.with_day(1)?;
+    if date != first_of_month && !history.iter().any(|(day, _)| *day == first_of_month) {
+        return None;
     }
+    let earlier = history
+        .iter()
+        .filter(|(day, daily)| *day >= first_of_month && *day < date && daily.difficulty == Some(difficulty))
+        .count();
+    Some(format!("\n{} {} {} this month", difficulty.emoji(), ordinal(earlier + 1), difficulty))
+}
+
+// Point out the problem's hints without giving them away, `None` if it has none
+fn format_hints(question: &DailyQuestion) -> Option<String> {
+    match question.hints {
+        0 => None,
+        1 => Some("\n💡 1 hint available".to_string()),
+        count => Some(format!("\n💡 {} hints available", count)),
+    }
+}
+
+// Describe the bonus setting, e.g. "on (Hard)"
+fn format_bonus(settings: &ChatSettings) -> String {
+    match (settings.bonus, settings.bonus_difficulty) {
+        (false, _) => "off".to_string(),
+        (true, Some(difficulty)) => format!("on ({})", difficulty),
+        (true, None) => "on".to_string(),
+    }
+}
+
+// Shortest time between two /feedback messages from the same chat
+const FEEDBACK_INTERVAL: Duration = Duration::from_secs(60);
 
+// Reply for admin commands sent from any other chat
+const NOT_AUTHORIZED: &str = "You are not authorized to use this command.";
+
+// Errors meaning the bot can never reach the chat again, so it should be unsubscribed
+fn is_unreachable(err: &RequestError) -> bool {
+    matches!(
+        err,
+        RequestError::Api(
+            ApiError::BotBlocked
+                | ApiError::ChatNotFound
+                | ApiError::BotKicked
+                | ApiError::BotKickedFromSupergroup
+                | ApiError::UserDeactivated
+                | ApiError::GroupDeactivated
+                | ApiError::CantInitiateConversation
+        )
+    )
 }
 
-// Save chat IDs to the file
-async fn save_chat_ids(file_path: &str, chat_ids: &HashSet<ChatId>) {
-    println!("Saving chat IDs to file...");
-    if let Ok(data) = serde_json::to_string(chat_ids) {
-        // Use tokio::fs::File for async file handling
-        if let Ok(mut file) = async_fs::File::create(file_path).await {
-            if file.write_all(data.as_bytes()).await.is_ok() {
-                if file.sync_all().await.is_ok() {
-                    println!("Chat IDs saved.");
-                } else {
-                    println!("Failed to sync data to disk.");
+
+// Look up every subscribed chat and unsubscribe those the bot was removed from, so dead chats don't pile up
+#[instrument(name = "startup_prune", skip_all)]
+async fn prune_unreachable_chats(app: &App) {
+    let chats = app.subscriptions.snapshot().await;
+    info!(chats = chats.len(), "Checking that subscribed chats are still reachable...");
+    let mut pruned = 0;
+    for (chat_id, _) in chats {
+        match send_with_flood_control(app, chat_id, app.bot.get_chat(chat_id)).await {
+            Ok(_) => {}
+            Err(err) if is_unreachable(&err) => {
+                pruned += 1;
+                if app.dry_run {
+                    info!(%chat_id, error = ?err, "Dry run, not unsubscribing unreachable chat.");
+                    continue;
                 }
-            } else {
-                println!("Failed to write data to file.");
+                warn!(%chat_id, error = ?err, "Chat is unreachable, unsubscribing.");
+                let result = app.subscriptions.unsubscribe(chat_id).await;
+                welcome_from_waitlist(app, result.promoted).await;
             }
-        } else {
-            println!("Failed to create file.");
+            // Anything else may be temporary, so the chat is kept
+            Err(err) => warn!(%chat_id, error = ?err, "Failed to check chat, keeping it."),
         }
+    }
+    info!(pruned, remaining = app.subscriptions.len().await, "Finished checking subscribed chats.");
+}
+
+// Parse a list like "easy,medium" into a difficulty filter, "all" clears the filter
+fn parse_difficulties(argument: &str) -> Option<BTreeSet<Difficulty>> {
+    if argument.eq_ignore_ascii_case("all") {
+        return Some(BTreeSet::new());
+    }
+    argument
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .map(Difficulty::parse)
+        .collect()
+}
+
+// Parse a list like "mon,tue,fri" into weekdays, Monday first; "all" means every day, "weekdays" Monday to Friday
+fn parse_weekdays(argument: &str) -> Option<Vec<Weekday>> {
+    let mut days: Vec<Weekday> = match argument.to_ascii_lowercase().as_str() {
+        "all" => return Some(Vec::new()),
+        "weekdays" => vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri],
+        "weekends" => vec![Weekday::Sat, Weekday::Sun],
+        _ => argument
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|part| !part.is_empty())
+            .map(|part| part.parse().ok())
+            .collect::<Option<_>>()?,
+    };
+    days.sort_by_key(|day| day.num_days_from_monday());
+    days.dedup();
+    match days.len() {
+        0 => None,
+        // Every day is stored as the default
+        7 => Some(Vec::new()),
+        _ => Some(days),
+    }
+}
+
+// "every day" or "on Mon, Tue" for replies
+fn format_days_phrase(days: &[Weekday]) -> String {
+    if days.is_empty() {
+        format_weekdays(days)
     } else {
-        println!("Failed to serialize chat IDs.");
+        format!("on {}", format_weekdays(days))
     }
 }
 
-#[tokio::main]
-async fn main() {
-    // Load environment variables
-    dotenv().ok();
-    println!("Loading environment variables...");
-    let bot_token = env::var("TELOXIDE_TOKEN").expect("TELOXIDE_TOKEN not set");
-    let trigger_time_str = env::var("TRIGGER_TIME").expect("TRIGGER_TIME not set");
-    let trigger_time = NaiveTime::parse_from_str(&trigger_time_str, "%H:%M:%S")
-        .expect("TRIGGER_TIME should be in the format HH:MM:SS");
-    let chat_ids_file_path = env::var("CHAT_IDS_FILE_PATH").expect("CHAT_IDS_FILE_PATH not set");
+// Describe the delivery days for replies
+fn format_weekdays(days: &[Weekday]) -> String {
+    if days.is_empty() {
+        return "every day".to_string();
+    }
+    days.iter().map(Weekday::to_string).collect::<Vec<_>>().join(", ")
+}
 
-    // Initialize the bot and HTTP client
-    println!("Initializing bot and client...");
-    let bot = Bot::new(bot_token);
-    let client = Client::new();
-
-    // Load chat IDs from the file
-    println!("Loading chat IDs from file...");
-    let chat_ids = Arc::new(Mutex::new(load_chat_ids(&chat_ids_file_path).await));
-    println!("Chat IDs loaded.");
-    // Calculate the duration until the next trigger time
-    let duration = duration_until_next_trigger(trigger_time);
-    let start = Instant::now() + duration;
+// Parse a list like "graph,dp" into tag slugs, "all" clears the filter
+fn parse_tags(argument: &str) -> BTreeSet<String> {
+    if argument.eq_ignore_ascii_case("all") {
+        return BTreeSet::new();
+    }
+    argument.split(',').map(tag_slug).filter(|slug| !slug.is_empty()).collect()
+}
 
-    // Clone necessary references for the spawned task
-    let bot_clone = bot.clone();
-    let client_clone = client.clone();
-    let chat_ids_clone = Arc::clone(&chat_ids);
+// Describe a tag filter for replies
+fn format_tags(tags: &BTreeSet<String>) -> String {
+    if tags.is_empty() {
+        return "all".to_string();
+    }
+    tags.iter().map(|tag| html::escape(tag)).collect::<Vec<_>>().join(", ")
+}
 
-    // Spawn a task to send the daily challenges at the trigger time
-    println!("Spawning task to send daily challenges...");
-    tokio::spawn(async move {
-        let mut interval = interval_at(start, Duration::from_secs(60 * 60 * 24));
-        loop {
-            println!("Waiting for next trigger...");
-            interval.tick().await;
-            println!("Triggered.");
-            if let Err(err) = send_daily_challenge(bot_clone.clone(), Arc::clone(&chat_ids_clone), client_clone.clone()).await {
-                eprintln!("Error sending daily challenge: {:?}", err);
+// Describe a difficulty filter for replies
+fn format_difficulties(difficulties: &BTreeSet<Difficulty>) -> String {
+    if difficulties.is_empty() {
+        return "all".to_string();
+    }
+    difficulties.iter().map(Difficulty::to_string).collect::<Vec<_>>().join(", ")
+}
+
+// State shared by the scheduler and the message handler
+struct App {
+    bot: Bot,
+    client: LeetCodeClient,
+    subscriptions: Subscriptions,
+    // Delivery time for chats that didn't set their own
+    trigger_time: NaiveTime,
+    // Longest random delay before sending to a chat
+    send_jitter: Duration,
+    // Limits how many chats are sent to at the same time, shared by all runs
+    send_permits: Semaphore,
+    // History of daily questions shared with the subscriptions
+    storage: Arc<dyn Storage>,
+    // When the weekly recap is sent, in each chat's timezone
+    weekly_day: Weekday,
+    weekly_time: NaiveTime,
+    // How long after the daily a solve reminder is sent
+    reminder_delay: Duration,
+    // How long a scheduled run retries when LeetCode has no daily yet
+    daily_retry_window: Duration,
+    // When the bot started, for /stats
+    started_at: DateTime<Utc>,
+    // Outcome of the latest daily run, for /stats
+    last_run: Mutex<Option<(DateTime<Utc>, SendSummary)>>,
+    // Longest problem statement preview, in characters
+    preview_chars: usize,
+    // Appended to every daily message, none if MESSAGE_FOOTER is empty
+    message_footer: Option<String>,
+    // Today's daily question, so repeated sends and /today don't ask LeetCode again
+    daily_cache: Mutex<Option<(NaiveDate, DailyQuestion)>>,
+    // First and latest day the daily response didn't match the expected schema, and whether the admin was told
+    schema_drift: Mutex<Option<SchemaDrift>>,
+    // Recently fetched LeetCode profiles by lowercase username, so /leaderboard doesn't ask for every member each time
+    profile_cache: Mutex<HashMap<String, (Instant, Profile)>>,
+    // When each chat last sent /feedback, to limit spam
+    last_feedback: Mutex<HashMap<ChatId, Instant>>,
+    // Shared by every bulk send so they stay under Telegram's flood limit together
+    rate_limiter: RateLimiter,
+    // Log outgoing bulk messages instead of sending them
+    dry_run: bool,
+    // Send the daily even when LeetCode still returns yesterday's after fetching again
+    send_repeated_daily: bool,
+    // New subscribers wait for the next scheduled daily instead of getting today's right away
+    quiet_start: bool,
+    // Chat allowed to use admin commands, none if ADMIN_CHAT_ID isn't set
+    admin_chat_id: Option<ChatId>,
+    // Sends that failed for good, oldest first, for /deadletters
+    dead_letters: Mutex<VecDeque<DeadLetter>>,
+    // Set by the admin's /pause, the scheduler skips every run while it's set
+    paused: AtomicBool,
+    // Chats the admin routed difficulties to with /route
+    routes: Mutex<Routes>,
+    // Problem the admin's /override sends instead of the daily
+    featured: Mutex<Option<FeaturedProblem>>,
+    // Every problem, refreshed in the background, that random picks are drawn from
+    problem_index: ProblemIndex,
+    // Channel getting run summaries and fetch errors, none if LOG_CHANNEL_ID isn't set
+    log_channel_id: Option<ChatId>,
+    // Readiness and last successful run, reported by the health server
+    health: Arc<Health>,
+}
+
+// Outcome of sending a message to a group of chats
+#[derive(Clone, Copy, Debug, Default)]
+struct SendSummary {
+    sent: usize,
+    failed: usize,
+    // Failed chats that were unsubscribed because they can't be reached anymore
+    removed: usize,
+    // Sent chats whose message couldn't be pinned
+    unpinned: usize,
+}
+
+impl SendSummary {
+    fn record(&mut self, delivery: SendOutcome) {
+        match delivery {
+            SendOutcome::Sent => self.sent += 1,
+            SendOutcome::Failed => self.failed += 1,
+            SendOutcome::Removed => {
+                self.failed += 1;
+                self.removed += 1;
             }
         }
-    });
+    }
 
-    // Handle incoming messages
-    println!("Starting message handler...");
-    let handler = Update::filter_message().branch(dptree::entry().endpoint({
-        let chat_ids = Arc::clone(&chat_ids);
-        let client_clone = client.clone();
-        let bot_clone = bot.clone();
-        let chat_ids_file_path = chat_ids_file_path.clone();
-        move |message: Message, bot: Bot| {
-            let chat_id = message.chat.id;
-            let text = message.text().unwrap_or_default().to_string();
-            let chat_ids = Arc::clone(&chat_ids);
-            let client_clone = client_clone.clone();
-            let bot_clone = bot_clone.clone();
-            let chat_ids_file_path = chat_ids_file_path.clone();
-            async move {
-                match text.as_str() {
-                    "/start" => {
-                        println!("Chat {} started receiving challenges.", chat_id);
-                        {
-                            let mut chat_ids_guard = chat_ids.lock().await;
-                            chat_ids_guard.insert(chat_id);
-                            save_chat_ids(&chat_ids_file_path, &chat_ids_guard).await;
-                        }
-                        bot.send_message(chat_id, "You will start receiving daily challenges.")
-                            .send()
-                            .await?;
+    fn record_attempt(&mut self, attempt: &Attempt) {
+        self.record(attempt.outcome());
+        if matches!(attempt, Attempt::Sent { pinned: false }) {
+            self.unpinned += 1;
+        }
+    }
+}
 
-                        // Send the first set of challenges immediately
-                        if let Err(err) = send_daily_challenge(bot_clone, Arc::clone(&chat_ids), client_clone).await {
-                            eprintln!("Error sending initial challenges: {:?}", err);
-                        }
-                    }
-                    "/stop" => {
-                        println!("Chat {} stopped receiving challenges.", chat_id);
-                        {
-                            let mut chat_ids_guard = chat_ids.lock().await;
-                            chat_ids_guard.remove(&chat_id);
-                            save_chat_ids(&chat_ids_file_path, &chat_ids_guard).await;
-                        }
-                        bot.send_message(chat_id, "You have stopped receiving daily challenges.")
-                            .send()
-                            .await?;
-                    }
-                    _ => {
-                        // do nothing
-                    }
-                }
-                respond(())
-            }
+impl fmt::Display for SendSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} sent, {} failed, {} removed", self.sent, self.failed, self.removed)?;
+        if self.unpinned > 0 {
+            write!(f, ", pin failed in {}", self.unpinned)?;
         }
-    }));
+        Ok(())
+    }
+}
 
-    Dispatcher::builder(bot, handler)
-        .enable_ctrlc_handler()
-        .build()
-        .dispatch()
+// In dry-run mode, log the message that would have been sent and tell the caller to skip it
+fn skip_for_dry_run(app: &App, chat_id: ChatId, text: &str) -> bool {
+    if app.dry_run {
+        info!(%chat_id, text, "Dry run, not sending message.");
+    }
+    app.dry_run
+}
+
+// How one attempt at sending to a chat ended
+#[derive(Clone, Debug)]
+enum Attempt {
+    // Delivered even if the pin didn't happen, `pinned` is false only when pinning was wanted and failed
+    Sent { pinned: bool },
+    // The chat can't be reached anymore and was unsubscribed
+    Removed,
+    // Telegram or the network rejected the send; transient failures are worth retrying
+    Failed { error: String, transient: bool },
+}
+
+impl Attempt {
+    fn outcome(&self) -> SendOutcome {
+        match self {
+            Attempt::Sent { .. } => SendOutcome::Sent,
+            Attempt::Removed => SendOutcome::Removed,
+            Attempt::Failed { .. } => SendOutcome::Failed,
+        }
+    }
+}
+
+// Log a failed send, unsubscribing the chat if it can't be reached anymore
+async fn check_sent(app: &App, chat_id: ChatId, sent: ResponseResult<Message>) -> Result<Message, Attempt> {
+    match sent {
+        Ok(message) => Ok(message),
+        Err(err) if is_unreachable(&err) => {
+            warn!(%chat_id, error = ?err, "Chat is unreachable, unsubscribing.");
+            let result = app.subscriptions.unsubscribe(chat_id).await;
+            welcome_from_waitlist(app, result.promoted).await;
+            Err(Attempt::Removed)
+        }
+        Err(err) => {
+            error!(%chat_id, error = ?err, "Failed to send message to chat.");
+            Err(Attempt::Failed { error: err.to_string(), transient: error::is_transient(&err) })
+        }
+    }
+}
+
+// Tell chats that left the waitlist that they are now subscribed
+async fn welcome_from_waitlist(app: &App, chat_ids: Vec<ChatId>) {
+    for chat_id in chat_ids {
+        info!(%chat_id, "Chat subscribed from the waitlist.");
+        let text = "A spot opened up, you will now receive daily challenges. Send /help to see all commands.";
+        if skip_for_dry_run(app, chat_id, text) {
+            continue;
+        }
+        if let Err(err) = app.bot.send_message(chat_id, text).send().await {
+            warn!(%chat_id, error = ?err, "Failed to tell chat it left the waitlist.");
+        }
+    }
+}
+
+// Post the message in the chat's forum topic, or the general thread if none was recorded
+fn in_thread(request: JsonRequest<SendMessage>, settings: &ChatSettings) -> JsonRequest<SendMessage> {
+    match settings.thread_id {
+        Some(thread_id) => request.message_thread_id(thread_id),
+        None => request,
+    }
+}
+
+// Forum topic the message was sent in, `None` for the general thread and chats without topics
+fn topic_thread_id(message: &Message) -> Option<i32> {
+    match &message.kind {
+        MessageKind::Common(common) if common.is_topic_message => message.thread_id,
+        _ => None,
+    }
+}
+
+// How a challenge is delivered to one chat; the staggering policy is up to the caller
+#[derive(Clone, Debug)]
+struct SendOptions {
+    // Wait before sending, zero for on-demand sends
+    delay: Duration,
+    // Whether to pin the message
+    pin: bool,
+    // Buttons shown under the message
+    keyboard: InlineKeyboardMarkup,
+}
+
+// Callback data of the buttons under the daily message
+const CALLBACK_SOLVED: &str = "solved";
+const CALLBACK_RANDOM: &str = "random";
+
+// Buttons under a challenge message; the link button is left out when there's no valid problem link
+fn daily_keyboard(lang: Lang, link: Option<&str>) -> InlineKeyboardMarkup {
+    let mut row = Vec::new();
+    if let Some(url) = link.and_then(|link| link.parse().ok()) {
+        row.push(InlineKeyboardButton::url(i18n::text(lang, Key::OpenButton), url));
+    }
+    row.push(InlineKeyboardButton::callback(i18n::text(lang, Key::SolvedButton), CALLBACK_SOLVED));
+    row.push(InlineKeyboardButton::callback(i18n::text(lang, Key::RandomButton), CALLBACK_RANDOM));
+    InlineKeyboardMarkup::new([row])
+}
+
+// Send and optionally pin the challenge in one chat, unsubscribing it if it's gone
+async fn send_to_chat(app: &App, chat_id: ChatId, settings: &ChatSettings, message_text: String, options: SendOptions) -> Attempt {
+    if !options.delay.is_zero() {
+        debug!(%chat_id, delay_secs = options.delay.as_secs(), "Sending message to chat with a delay...");
+        sleep(options.delay).await;
+    }
+    let _permit = app.send_permits.acquire().await.expect("Send semaphore closed");
+    METRICS.sends_attempted.inc();
+    if skip_for_dry_run(app, chat_id, &message_text) {
+        return Attempt::Sent { pinned: true };
+    }
+    let mut anchor = match (settings.reply_thread, settings.anchor_message_id) {
+        (false, _) => None,
+        (true, Some(anchor)) => Some(MessageId(anchor)),
+        (true, None) => post_anchor(app, chat_id, settings).await,
+    };
+    let mut sent = send_long_message(app, chat_id, settings, &message_text, &options.keyboard, anchor).await;
+    if anchor.is_some() && matches!(sent, Err(RequestError::Api(ApiError::MessageToReplyNotFound))) {
+        info!(%chat_id, "The thread anchor was deleted, posting a new one.");
+        anchor = post_anchor(app, chat_id, settings).await;
+        sent = send_long_message(app, chat_id, settings, &message_text, &options.keyboard, anchor).await;
+    }
+    let message = match check_sent(app, chat_id, sent).await {
+        Ok(message) => message,
+        Err(attempt) => return attempt,
+    };
+    // In a thread the earlier dailies are its history and the anchor stays pinned, so they're left alone
+    if settings.auto_unpin && anchor.is_none() {
+        if let Some(previous) = settings.last_message_id.filter(|&previous| previous != message.id.0) {
+            clear_previous_message(app, chat_id, MessageId(previous), settings.delete_previous).await;
+        }
+    }
+    // A failed pin doesn't undo the send; the message already sits in the chat's topic, so pinning needs no thread
+    let mut pinned = true;
+    if options.pin {
+        let request = app.bot.pin_chat_message(chat_id, anchor.unwrap_or(message.id)).disable_notification(!settings.pin_notify);
+        match send_with_flood_control(app, chat_id, request).await {
+            Ok(_) => {}
+            // Someone deleted the message between the send and the pin; teloxide has no variant for this error
+            Err(RequestError::Api(ApiError::Unknown(description))) if description.contains("message to pin not found") => {
+                info!(%chat_id, "Message was deleted before it could be pinned.");
+                pinned = false;
+            }
+            Err(err) => {
+                warn!(%chat_id, error = ?err, "Failed to pin message.");
+                pinned = false;
+            }
+        }
+    }
+    info!(%chat_id, "Message sent to chat.");
+    let today = local_now(Utc::now(), settings.timezone).date();
+    app.subscriptions
+        .update(chat_id, |settings| {
+            settings.last_broadcast_date = Some(today);
+            settings.last_message_id = Some(message.id.0);
+        })
         .await;
+    Attempt::Sent { pinned }
+}
+
+// Telegram's limit on the length of one message
+const MAX_MESSAGE_CHARS: usize = 4096;
+
+// Split a message into parts under the limit, preferring paragraph breaks, then line breaks, then any point outside
+// a tag or an entity. Tags still open where a part ends are closed there and opened again in the next part, so a
+// preview or a code block cut in two still parses.
+fn split_message(text: &str, limit: usize) -> Vec<String> {
+    let parts = split_on(text, &["\n\n", "\n"], limit).into_iter().filter(|part| !part.trim().is_empty()).collect();
+    balance_tags(parts)
+}
+
+fn split_on(text: &str, separators: &[&str], limit: usize) -> Vec<String> {
+    if visible_len(text) <= limit {
+        return vec![text.to_string()];
+    }
+    let Some((separator, finer)) = separators.split_first() else {
+        return split_anywhere(text, limit);
+    };
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    for piece in text.split(separator).flat_map(|section| split_on(section, finer, limit)) {
+        if current.is_empty() {
+            current = piece;
+        } else if visible_len(&current) + separator.len() + visible_len(&piece) > limit {
+            parts.push(std::mem::replace(&mut current, piece));
+        } else {
+            current.push_str(separator);
+            current.push_str(&piece);
+        }
+    }
+    parts.push(current);
+    parts
+}
+
+// Length of HTML as Telegram counts it, without tags and with each entity as one character
+fn visible_len(html: &str) -> usize {
+    let mut len = 0;
+    let mut in_tag = false;
+    let mut in_entity = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if in_tag => {}
+            '&' => {
+                in_entity = true;
+                len += 1;
+            }
+            ';' if in_entity => in_entity = false,
+            _ if in_entity => {}
+            _ => len += 1,
+        }
+    }
+    len
+}
+
+// Cut text without line breaks into parts of at most `limit` visible characters, never inside a tag or an entity
+fn split_anywhere(text: &str, limit: usize) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut len = 0;
+    // Byte offset in `current` of the last point outside a tag or an entity, and the visible length before it
+    let mut cut = (0, 0);
+    let mut in_markup = false;
+    for c in text.chars() {
+        if !in_markup {
+            cut = (current.len(), len);
+        }
+        current.push(c);
+        match c {
+            '<' => in_markup = true,
+            '&' => {
+                in_markup = true;
+                len += 1;
+            }
+            '>' | ';' if in_markup => in_markup = false,
+            _ if in_markup => {}
+            _ => len += 1,
+        }
+        if len > limit && cut.0 > 0 {
+            let rest = current.split_off(cut.0);
+            parts.push(std::mem::replace(&mut current, rest));
+            len -= cut.1;
+            cut = (0, 0);
+        }
+    }
+    parts.push(current);
+    parts
+}
+
+// Close the tags still open at the end of each part and open them again at the start of the next
+fn balance_tags(parts: Vec<String>) -> Vec<String> {
+    // Opening tags as written, e.g. <code class="language-python3">, outermost first
+    let mut open: Vec<String> = Vec::new();
+    parts
+        .into_iter()
+        .map(|part| {
+            let mut balanced = open.concat();
+            balanced.push_str(&part);
+            track_open_tags(&part, &mut open);
+            for tag in open.iter().rev() {
+                balanced.push_str(&format!("</{}>", tag_name(tag)));
+            }
+            balanced
+        })
+        .collect()
+}
+
+// Update the stack of open tags with the tags in the HTML; escaped text has no other '<'
+fn track_open_tags(html: &str, open: &mut Vec<String>) {
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        let Some(len) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start..=start + len];
+        if tag.starts_with("</") {
+            if let Some(index) = open.iter().rposition(|open| tag_name(open) == tag_name(tag)) {
+                open.truncate(index);
+            }
+        } else {
+            open.push(tag.to_string());
+        }
+        rest = &rest[start + len + 1..];
+    }
+}
+
+// Name of an opening or closing tag, e.g. "a" for <a href="..."> and </a>
+fn tag_name(tag: &str) -> &str {
+    tag.trim_start_matches("</").trim_start_matches('<').trim_end_matches('>').split_whitespace().next().unwrap_or_default()
+}
+
+// Send a message that may be over Telegram's limit as several in a row, returning the first; the buttons go
+// under the first, which is the one pinned and remembered, and failures of later parts are only logged
+async fn send_long_message(
+    app: &App,
+    chat_id: ChatId,
+    settings: &ChatSettings,
+    text: &str,
+    keyboard: &InlineKeyboardMarkup,
+    reply_to: Option<MessageId>,
+) -> ResponseResult<Message> {
+    let request = |text: String| {
+        let request = in_thread(
+            app.bot.send_message(chat_id, text)
+                .parse_mode(ParseMode::Html)
+                .disable_web_page_preview(!settings.link_preview),
+            settings,
+        );
+        match reply_to {
+            Some(message_id) => request.reply_to_message_id(message_id),
+            None => request,
+        }
+    };
+    let mut parts = split_message(text, MAX_MESSAGE_CHARS).into_iter();
+    let first = parts.next().unwrap_or_default();
+    let message = send_with_flood_control(app, chat_id, request(first).reply_markup(keyboard.clone())).await?;
+    for (index, part) in parts.enumerate() {
+        if let Err(err) = send_with_flood_control(app, chat_id, request(part).disable_web_page_preview(true)).await {
+            warn!(%chat_id, part = index + 2, error = ?err, "Failed to send part of a long message.");
+            break;
+        }
+    }
+    Ok(message)
+}
+
+// Post a new anchor message for the chat's daily thread and remember it; without one the daily is sent on its own
+async fn post_anchor(app: &App, chat_id: ChatId, settings: &ChatSettings) -> Option<MessageId> {
+    let request = in_thread(app.bot.send_message(chat_id, i18n::text(settings.lang, Key::ThreadAnchor)), settings);
+    let anchor = match send_with_flood_control(app, chat_id, request).await {
+        Ok(anchor) => anchor.id,
+        Err(err) => {
+            warn!(%chat_id, error = ?err, "Failed to post the thread anchor, sending the daily without it.");
+            return None;
+        }
+    };
+    info!(%chat_id, anchor = anchor.0, "Posted a new thread anchor.");
+    app.subscriptions.update(chat_id, |settings| settings.anchor_message_id = Some(anchor.0)).await;
+    Some(anchor)
+}
+
+// Unpin the previous challenge message and optionally delete it; users may have removed it already, so failures are only logged
+async fn clear_previous_message(app: &App, chat_id: ChatId, message_id: MessageId, delete: bool) {
+    let request = app.bot.unpin_chat_message(chat_id).message_id(message_id);
+    if let Err(err) = send_with_flood_control(app, chat_id, request).await {
+        warn!(%chat_id, error = ?err, "Failed to unpin the previous message.");
+    }
+    if delete {
+        let request = app.bot.delete_message(chat_id, message_id);
+        if let Err(err) = send_with_flood_control(app, chat_id, request).await {
+            warn!(%chat_id, error = ?err, "Failed to delete the previous message.");
+        }
+    }
+}
+
+// Today's daily question, fetched from LeetCode once per UTC day and recorded in the history
+async fn cached_daily_question(app: &App) -> Result<Option<DailyQuestion>, BotError> {
+    // LeetCode switches to the next daily at midnight UTC
+    let today = Utc::now().date_naive();
+    // Holding the lock while fetching makes concurrent callers wait for one request instead of each sending their own
+    let mut cache = app.daily_cache.lock().await;
+    if let Some((date, question)) = cache.as_ref() {
+        if *date == today {
+            debug!("Using cached daily question.");
+            return Ok(Some(question.clone()));
+        }
+    }
+    let result = fetch_leetcode_daily_question(&app.client).await;
+    track_schema_drift(app, &result, today).await;
+    let mut daily_question = result?;
+    if let Some(question) = &daily_question {
+        if repeats_yesterday(app, question, today).await {
+            warn!(
+                title = %question.title,
+                delay_secs = REPEAT_REFETCH_DELAY.as_secs(),
+                "Daily question is the same as yesterday's, fetching it again."
+            );
+            sleep(REPEAT_REFETCH_DELAY).await;
+            let result = fetch_leetcode_daily_question(&app.client).await;
+            track_schema_drift(app, &result, today).await;
+            daily_question = result?;
+        }
+    }
+    if let Some(question) = &daily_question {
+        // A repeat stays out of the history so the weekly recap doesn't list it twice
+        if repeats_yesterday(app, question, today).await {
+            warn!(title = %question.title, "Daily question is still the same as yesterday's.");
+        } else {
+            app.storage.record_daily(today, question).await;
+        }
+        *cache = Some((today, question.clone()));
+    }
+    Ok(daily_question)
+}
+
+// How often a scheduled run asks LeetCode again while the daily isn't available
+const DAILY_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+// Keep fetching today's daily every `interval` until it's available or `daily_retry_window` runs out, then tell the admin
+async fn wait_for_daily_question(app: &App, interval: Duration) -> bool {
+    let deadline = Instant::now() + app.daily_retry_window;
+    let text = loop {
+        match cached_daily_question(app).await {
+            Ok(Some(_)) => return true,
+            Ok(None) => warn!("LeetCode has no daily question yet."),
+            // Asking again won't parse a response of a changed schema, while a rate limit or an outage may pass
+            Err(err @ BotError::Parse(_)) => {
+                error!(error = ?err, "The daily question can't be read, nothing was sent.");
+                break format!("The daily question couldn't be read, so today's challenge wasn't sent: {}", err);
+            }
+            Err(err) => {
+                warn!(error = ?err, "Error fetching the daily question.");
+                post_to_log_channel(app, format!("Error fetching the daily question: {}", err)).await;
+            }
+        }
+        if Instant::now() + interval > deadline {
+            error!(window_secs = app.daily_retry_window.as_secs(), "The daily question never became available, nothing was sent.");
+            break format!(
+                "The daily question wasn't available after {} minutes of retries, so today's challenge wasn't sent.",
+                app.daily_retry_window.as_secs() / 60
+            );
+        }
+        sleep(interval).await;
+    };
+    post_to_log_channel(app, text.clone()).await;
+    if let Some(admin_chat_id) = app.admin_chat_id {
+        if let Err(err) = app.bot.send_message(admin_chat_id, text).send().await {
+            warn!(error = ?err, "Failed to tell the admin about the missing daily.");
+        }
+    }
+    false
+}
+
+// How long to wait before asking LeetCode again when the daily is the same as yesterday's
+const REPEAT_REFETCH_DELAY: Duration = Duration::from_secs(30);
+
+// Whether the question was already yesterday's daily, which happens when LeetCode serves a stale answer around midnight
+async fn repeats_yesterday(app: &App, question: &DailyQuestion, today: NaiveDate) -> bool {
+    let Some(yesterday) = today.pred_opt() else {
+        return false;
+    };
+    match app.storage.dailies_since(yesterday).await {
+        Ok(dailies) => dailies.iter().any(|(date, daily)| *date == yesterday && daily.link == question.link),
+        Err(err) => {
+            warn!(error = ?err, "Failed to read the daily history, assuming the daily is new.");
+            false
+        }
+    }
+}
+
+// Days in a row the daily response has to be malformed before the admin is told
+const SCHEMA_DRIFT_ALERT_DAYS: i64 = 3;
+
+// Run of days on which LeetCode's daily response didn't match the expected schema
+#[derive(Clone, Copy, Debug)]
+struct SchemaDrift {
+    since: NaiveDate,
+    last: NaiveDate,
+    notified: bool,
+}
+
+// Log schema problems distinctly from outages, and tell the admin once they persist for several days
+async fn track_schema_drift(app: &App, result: &Result<Option<DailyQuestion>, BotError>, today: NaiveDate) {
+    let mut drift = app.schema_drift.lock().await;
+    let problem = match result {
+        Err(BotError::Parse(problem)) => problem,
+        Ok(_) => {
+            if drift.take().is_some() {
+                info!("LeetCode's daily response matches the expected schema again.");
+            }
+            return;
+        }
+        Err(_) => return,
+    };
+    METRICS.schema_errors.inc();
+    let current = match *drift {
+        Some(current) if today - current.last <= chrono::Duration::days(1) => SchemaDrift { last: today, ..current },
+        _ => SchemaDrift { since: today, last: today, notified: false },
+    };
+    let days = (current.last - current.since).num_days() + 1;
+    error!(problem, days, "LeetCode's daily response doesn't match the expected schema, the GraphQL query may need updating.");
+    *drift = Some(current);
+    if days < SCHEMA_DRIFT_ALERT_DAYS || current.notified {
+        return;
+    }
+    let Some(admin_chat_id) = app.admin_chat_id else {
+        return;
+    };
+    let text = format!(
+        "The daily question couldn't be parsed for {} days in a row, LeetCode may have changed its GraphQL schema.\nLatest problem: {}",
+        days, problem
+    );
+    match app.bot.send_message(admin_chat_id, text).send().await {
+        Ok(_) => *drift = Some(SchemaDrift { notified: true, ..current }),
+        Err(err) => warn!(error = ?err, "Failed to tell the admin about the schema problem."),
+    }
+}
+
+// The admin's /override to send on UTC day `date` instead of the daily. The first scheduled run after /override claims
+// it for its day, so every chat gets it that day whatever its delivery time, and it's dropped once that day is over.
+async fn featured_problem(app: &App, date: NaiveDate, scheduled: bool) -> Option<DailyQuestion> {
+    let mut featured = app.featured.lock().await;
+    let claimed = featured.as_ref()?.date;
+    match claimed {
+        Some(day) if day == date => {}
+        Some(day) if day < date => {
+            info!(%day, "The featured problem's day is over, sending the daily again.");
+            *featured = None;
+            app.storage.save_featured(None).await;
+            return None;
+        }
+        Some(_) => return None,
+        None if scheduled => {
+            let current = featured.as_mut()?;
+            current.date = Some(date);
+            info!(title = %current.question.title, %date, "Sending the featured problem instead of the daily.");
+            app.storage.save_featured(featured.as_ref()).await;
+        }
+        None => return None,
+    }
+    featured.as_ref().map(|featured| featured.question.clone())
+}
+
+// Describe the /override state for the admin
+fn format_featured(featured: Option<&FeaturedProblem>) -> String {
+    match featured {
+        None => "No override set. Use /override <slug|number> to send a chosen problem instead of the next daily.".to_string(),
+        Some(FeaturedProblem { question, date: None }) => {
+            format!("The next scheduled send features {} instead of the daily. /override clear cancels it.", format_question(question))
+        }
+        Some(FeaturedProblem { question, date: Some(date) }) => {
+            format!("{} is featured instead of the daily on {} (UTC). /override clear cancels it.", format_question(question), date)
+        }
+    }
+}
+
+// Resolve what today's sends feature: the admin's /override, otherwise the daily unless it repeats yesterday's
+async fn todays_challenge(app: &App, scheduled: bool) -> Result<Option<Challenge>, BotError> {
+    if let Some(question) = featured_problem(app, Utc::now().date_naive(), scheduled).await {
+        return Ok(Some(Challenge { question, featured: true }));
+    }
+    daily_challenge(app).await
+}
+
+// Today's daily as a challenge, `None` if LeetCode has none or it repeats yesterday's
+async fn daily_challenge(app: &App) -> Result<Option<Challenge>, BotError> {
+    let today = Utc::now().date_naive();
+    // A "not available" message can't be taken back once the daily shows up, so nothing is sent without it
+    let Some(question) = cached_daily_question(app).await? else {
+        warn!("Skipping the run since LeetCode has no daily question.");
+        return Ok(None);
+    };
+    if !app.send_repeated_daily && repeats_yesterday(app, &question, today).await {
+        warn!(title = %question.title, "Skipping the run since the daily is the same as yesterday's.");
+        return Ok(None);
+    }
+    Ok(Some(Challenge { question, featured: false }))
+}
+
+// Send the challenge to the given chats, spread evenly over `jitter`, continuing past chats that fail
+#[instrument(name = "daily_run", skip_all, fields(chats = chat_ids.len()))]
+async fn send_daily_challenge(
+    app: Arc<App>,
+    mut chat_ids: Vec<(ChatId, ChatSettings)>,
+    jitter: Duration,
+    challenge: &Challenge,
+) -> SendSummary {
+    apply_routes(&mut chat_ids, &*app.routes.lock().await);
+    let is_featured = challenge.featured;
+    let daily_question = &challenge.question;
+    let label = if is_featured { Key::FeaturedLabel } else { Key::DailyLabel };
+
+    // Chats whose filter rejects the daily get a random problem instead, shared by chats with the same filter;
+    // the admin's featured problem goes to everyone
+    let mut picks: HashMap<ProblemFilter, Option<DailyQuestion>> = HashMap::new();
+    for (_, settings) in &chat_ids {
+        let filter = settings.problem_filter();
+        if is_featured || settings.accepts(daily_question) || picks.contains_key(&filter) {
+            continue;
+        }
+        let pick = match random_question(&app, &filter).await {
+            Ok(Some(question)) => Some(question),
+            Ok(None) => {
+                warn!(?filter, "No random question found, sending the daily instead.");
+                None
+            }
+            Err(err) => {
+                error!(error = ?err, "Error fetching random question, sending the daily instead.");
+                post_to_log_channel(&app, format!("Error fetching a random question for {:?}: {}", filter, err)).await;
+                None
+            }
+        };
+        picks.insert(filter, pick);
+    }
+
+    // Bonus problems are shared the same way; a chat whose bonus can't be fetched still gets its daily
+    let mut bonuses: HashMap<ProblemFilter, Option<DailyQuestion>> = HashMap::new();
+    for filter in chat_ids.iter().filter_map(|(_, settings)| settings.bonus_filter()) {
+        if bonuses.contains_key(&filter) {
+            continue;
+        }
+        let bonus = match random_question(&app, &filter).await {
+            Ok(bonus) => bonus,
+            Err(err) => {
+                error!(error = ?err, "Error fetching bonus question, sending without it.");
+                post_to_log_channel(&app, format!("Error fetching a bonus question for {:?}: {}", filter, err)).await;
+                None
+            }
+        };
+        bonuses.insert(filter, bonus);
+    }
+
+    // The history is the same for everyone, so it's only read once and only if a chat wants the breakdown
+    let run_date = Utc::now().date_naive();
+    let mut breakdown = None;
+    let wants_breakdown = !is_featured && chat_ids.iter().any(|(_, settings)| settings.breakdown);
+    if let (true, Some(first_of_month)) = (wants_breakdown, run_date.with_day(1)) {
+        match app.storage.dailies_since(first_of_month).await {
+            Ok(history) => breakdown = format_breakdown(run_date, daily_question, &history),
+            Err(err) => warn!(error = ?err, "Failed to read the daily history, sending without the breakdown."),
+        }
+    }
+
+    info!("Sending message to chats...");
+    let delays = send_delays(chat_ids.len(), jitter);
+    let mut tasks = JoinSet::new();
+    for ((chat_id, settings), delay) in chat_ids.into_iter().zip(delays) {
+        let message_text = match picks.get(&settings.problem_filter()) {
+            Some(Some(pick)) => match &settings.template {
+                Some(custom) => template::render(custom, pick),
+                None => format_daily_message(settings.lang, Key::PickLabel, Some(pick)),
+            },
+            _ => {
+                let mut text = match &settings.template {
+                    Some(custom) => template::render(custom, daily_question),
+                    None => format_daily_message(settings.lang, label, Some(daily_question)),
+                };
+                if settings.breakdown {
+                    text.push_str(breakdown.as_deref().unwrap_or_default());
+                }
+                if settings.preview {
+                    text.push_str(&format_preview(daily_question, app.preview_chars).unwrap_or_default());
+                }
+                if settings.companies {
+                    text.push_str(&format_companies(&daily_question.companies).unwrap_or_default());
+                }
+                if settings.hints {
+                    text.push_str(&format_hints(daily_question).unwrap_or_default());
+                }
+                text.push_str(&format_snippet(daily_question, settings.code_lang.as_deref()).unwrap_or_default());
+                text
+            }
+        };
+        let bonus = settings.bonus_filter().and_then(|filter| bonuses.get(&filter).cloned().flatten());
+        let message_text = match bonus {
+            // A random draw can land on the daily itself
+            Some(bonus) if bonus.link != daily_question.link => {
+                format!("{}\n\n{}: {}", message_text, i18n::text(settings.lang, Key::BonusLabel), format_question(&bonus))
+            }
+            _ => message_text,
+        };
+        let message_text = with_footer(message_text, app.message_footer.as_deref());
+        let sent_question = picks.get(&settings.problem_filter()).cloned().flatten().unwrap_or_else(|| daily_question.clone());
+        let options = SendOptions {
+            delay,
+            pin: !settings.pin_disabled,
+            keyboard: daily_keyboard(settings.lang, Some(&sent_question.link)),
+        };
+        let send = PendingSend { chat_id, settings, message_text, options, slug: Some(sent_question.slug) };
+        let app = Arc::clone(&app);
+        tasks.spawn(
+            async move {
+                let attempt = send.attempt(&app, run_date).await;
+                (send, attempt)
+            }
+            .in_current_span(),
+        );
+    }
+
+    let mut summary = SendSummary::default();
+    let mut retries = Vec::new();
+    while let Some(result) = tasks.join_next().await {
+        match result {
+            Ok((send, Attempt::Failed { error, transient: true })) => retries.push((send, error)),
+            Ok((_, attempt)) => summary.record_attempt(&attempt),
+            Err(err) => {
+                error!(error = ?err, "Send task panicked.");
+                summary.failed += 1;
+            }
+        }
+    }
+
+    // Transient failures get a few more tries once everyone else has their message
+    for (round, backoff) in SEND_RETRY_BACKOFF.iter().enumerate() {
+        if retries.is_empty() {
+            break;
+        }
+        info!(chats = retries.len(), round = round + 1, backoff_secs = backoff.as_secs(), "Retrying failed sends.");
+        sleep(*backoff).await;
+        let mut still_failing = Vec::new();
+        for (mut send, _) in retries {
+            // Fresh settings skip chats that unsubscribed meanwhile and keep a thread anchor posted by the failed try
+            let Some(settings) = app.subscriptions.get(send.chat_id).await else {
+                continue;
+            };
+            send.settings = settings;
+            send.options.delay = Duration::ZERO;
+            match send.attempt(&app, run_date).await {
+                Attempt::Failed { error, transient: true } => still_failing.push((send, error)),
+                attempt => summary.record_attempt(&attempt),
+            }
+        }
+        retries = still_failing;
+    }
+    for (send, error) in retries {
+        summary.record(SendOutcome::Failed);
+        send.record(&app, run_date, SendOutcome::Failed).await;
+        dead_letter(&app, send.chat_id, error, SEND_RETRY_BACKOFF.len() + 1).await;
+    }
+    METRICS.sends_succeeded.inc_by(summary.sent as u64);
+    METRICS.sends_failed.inc_by(summary.failed as u64);
+
+    info!(%summary, "Daily challenge sent.");
+    *app.last_run.lock().await = Some((Utc::now(), summary));
+    summary
+}
+
+// Waits before each retry of the sends that failed for a transient reason
+const SEND_RETRY_BACKOFF: [Duration; 3] = [Duration::from_secs(10), Duration::from_secs(30), Duration::from_secs(90)];
+
+// A challenge message for one chat, kept so a transient failure can be retried
+struct PendingSend {
+    chat_id: ChatId,
+    settings: ChatSettings,
+    message_text: String,
+    options: SendOptions,
+    slug: Option<String>,
+}
+
+impl PendingSend {
+    // Send once, logging the outcome unless it's a transient failure that will be retried
+    async fn attempt(&self, app: &App, run_date: NaiveDate) -> Attempt {
+        let attempt = send_to_chat(app, self.chat_id, &self.settings, self.message_text.clone(), self.options.clone()).await;
+        match &attempt {
+            Attempt::Failed { transient: true, .. } => {}
+            Attempt::Failed { error, transient: false } => {
+                self.record(app, run_date, SendOutcome::Failed).await;
+                dead_letter(app, self.chat_id, error.clone(), 1).await;
+            }
+            attempt => self.record(app, run_date, attempt.outcome()).await,
+        }
+        attempt
+    }
+
+    // Logged as each send finishes, so a crash mid-run still leaves the sends so far
+    async fn record(&self, app: &App, run_date: NaiveDate, outcome: SendOutcome) {
+        if app.dry_run {
+            return;
+        }
+        let record = SendRecord { at: Utc::now(), date: run_date, chat_id: self.chat_id, slug: self.slug.clone(), outcome };
+        app.storage.record_send(&record).await;
+    }
+}
+
+// Most dead letters kept for /deadletters, older ones are dropped
+const MAX_DEAD_LETTERS: usize = 100;
+
+// A challenge that couldn't be delivered even after retrying
+#[derive(Clone, Debug)]
+struct DeadLetter {
+    at: DateTime<Utc>,
+    chat_id: ChatId,
+    error: String,
+    attempts: usize,
+}
+
+// Remember a send that failed for good, for the admin to look at with /deadletters
+async fn dead_letter(app: &App, chat_id: ChatId, error: String, attempts: usize) {
+    warn!(%chat_id, attempts, error, "Giving up on sending to chat.");
+    let mut dead_letters = app.dead_letters.lock().await;
+    if dead_letters.len() == MAX_DEAD_LETTERS {
+        dead_letters.pop_front();
+    }
+    dead_letters.push_back(DeadLetter { at: Utc::now(), chat_id, error, attempts });
+}
+
+// Reply to /deadletters, newest first
+fn format_dead_letters(dead_letters: &VecDeque<DeadLetter>) -> String {
+    if dead_letters.is_empty() {
+        return "No failed sends since the bot started.".to_string();
+    }
+    let mut text = format!("<b>Failed sends</b> ({} kept, newest first)", dead_letters.len());
+    for letter in dead_letters.iter().rev().take(20) {
+        text.push_str(&format!(
+            "\n<code>{}</code> at {} after {} {}: {}",
+            letter.chat_id,
+            letter.at.format("%Y-%m-%d %H:%M"),
+            letter.attempts,
+            if letter.attempts == 1 { "attempt" } else { "attempts" },
+            html::escape(&letter.error)
+        ));
+    }
+    text
+}
+
+// Parse "hard -1001234,-1005678" into a difficulty and the chats it goes to, "off" for the chats clears the route
+fn parse_route(argument: &str) -> Option<(Difficulty, BTreeSet<ChatId>)> {
+    let (difficulty, chats) = argument.split_once(char::is_whitespace)?;
+    let difficulty = Difficulty::parse(difficulty)?;
+    let chats = chats.trim();
+    if chats.eq_ignore_ascii_case("off") {
+        return Some((difficulty, BTreeSet::new()));
+    }
+    let chat_ids = chats
+        .split([',', ' '])
+        .filter(|chat| !chat.is_empty())
+        .map(|chat| chat.parse().ok().map(ChatId))
+        .collect::<Option<BTreeSet<_>>>()?;
+    (!chat_ids.is_empty()).then_some((difficulty, chat_ids))
+}
+
+// Routed chats only take the difficulties routed to them, so like a /difficulty filter they get the daily when it
+// matches and a random problem of their difficulty otherwise
+fn apply_routes(chat_ids: &mut [(ChatId, ChatSettings)], routes: &Routes) {
+    for (chat_id, settings) in chat_ids {
+        let routed: BTreeSet<Difficulty> =
+            routes.iter().filter(|(_, chats)| chats.contains(chat_id)).map(|(difficulty, _)| *difficulty).collect();
+        if !routed.is_empty() {
+            settings.difficulties = routed;
+        }
+    }
+}
+
+// Reply to /version, e.g. "leetcode_bot 0.1.0 (commit 1a2b3c4)"
+fn format_version() -> String {
+    format!("{} {} (commit {})", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("GIT_COMMIT"))
+}
+
+// Reply to /count, without any chat IDs
+fn format_count(subscribed: bool, chats: usize, timezones: usize) -> String {
+    let mut text = format!(
+        "{} {} the daily challenge across {} {}.",
+        chats,
+        if chats == 1 { "chat gets" } else { "chats get" },
+        timezones,
+        if timezones == 1 { "timezone" } else { "timezones" }
+    );
+    if subscribed {
+        text.push_str(&format!(" This chat is one of {}!", chats));
+    }
+    text
+}
+
+// List the difficulty routes for the admin
+fn format_routes(routes: &Routes) -> String {
+    if routes.is_empty() {
+        return "No difficulty routes. Use /route <easy|medium|hard> <chat IDs|off> to send a difficulty to chosen chats."
+            .to_string();
+    }
+    let mut text = "<b>Difficulty routes</b>".to_string();
+    for (difficulty, chats) in routes {
+        let chats: Vec<String> = chats.iter().map(|chat_id| format!("<code>{}</code>", chat_id)).collect();
+        text.push_str(&format!("\n{} {}: {}", difficulty.emoji(), difficulty, chats.join(", ")));
+    }
+    text
+}
+
+// Post to the log channel if one is configured; it's only an operational feed, so failures are just logged
+async fn post_to_log_channel(app: &App, text: String) {
+    let Some(log_channel_id) = app.log_channel_id else {
+        return;
+    };
+    if let Err(err) = app.bot.send_message(log_channel_id, text).disable_web_page_preview(true).send().await {
+        warn!(error = ?err, "Failed to post to the log channel.");
+    }
+}
+
+// Summary of a scheduled run for the log channel
+fn format_run_report(question: Option<&DailyQuestion>, subscribers: usize, summary: SendSummary) -> String {
+    format!(
+        "Daily run: {}\n{} subscribers, {}",
+        question.map_or("no daily question", |question| question.title.as_str()),
+        subscribers,
+        summary
+    )
+}
+
+// Post the outcome of a scheduled run to the log channel
+async fn report_run(app: &App, question: &DailyQuestion, summary: SendSummary) {
+    if app.log_channel_id.is_none() {
+        return;
+    }
+    let text = format_run_report(Some(question), app.subscriptions.len().await, summary);
+    post_to_log_channel(app, text).await;
+}
+
+// Most failed chats listed by /log, so the reply stays under Telegram's message size limit
+const MAX_LOGGED_FAILURES: usize = 50;
+
+// Summarize a day of the send log for /log
+fn format_send_log(date: NaiveDate, records: &[SendRecord]) -> String {
+    if records.is_empty() {
+        return format!("<b>Send log for {}</b>\nNothing was sent that day.", date);
+    }
+    let sent = records.iter().filter(|record| record.outcome == SendOutcome::Sent).count();
+    let failed: Vec<&SendRecord> = records.iter().filter(|record| record.outcome != SendOutcome::Sent).collect();
+    let slugs: BTreeSet<&str> = records.iter().filter_map(|record| record.slug.as_deref()).collect();
+    let mut text = format!(
+        "<b>Send log for {}</b>\n{} sent, {} failed\nProblems: {}",
+        date,
+        sent,
+        failed.len(),
+        if slugs.is_empty() { "none".to_string() } else { html::escape(&slugs.into_iter().collect::<Vec<_>>().join(", ")) }
+    );
+    if !failed.is_empty() {
+        text.push_str("\nFailed chats:");
+        for record in failed.iter().take(MAX_LOGGED_FAILURES) {
+            let removed = if record.outcome == SendOutcome::Removed { ", unsubscribed" } else { "" };
+            text.push_str(&format!("\n<code>{}</code> at {}{}", record.chat_id, record.at.format("%H:%M:%S"), removed));
+        }
+        if failed.len() > MAX_LOGGED_FAILURES {
+            text.push_str(&format!("\n+{} more", failed.len() - MAX_LOGGED_FAILURES));
+        }
+    }
+    text
+}
+
+// Reply to /import without a file or with an unknown mode
+const IMPORT_USAGE: &str = "Send the file from /export with the caption /import [merge|replace], or reply to it with that command. Merge keeps the current chats, replace drops those missing from the file.";
+
+// Largest file /import downloads, far more than any real export
+const MAX_IMPORT_BYTES: u32 = 10 * 1024 * 1024;
+
+// Download a file sent to the bot into memory
+async fn download_document(bot: &Bot, file_id: &str) -> Result<Vec<u8>, BotError> {
+    let file = bot.get_file(file_id).send().await?;
+    let mut data = Vec::new();
+    bot.download_file(&file.path, &mut data).await?;
+    Ok(data)
+}
+
+// Delay before sending to each of `count` chats: the window is cut into equal slots with one send at a random point
+// of each, so sends don't clump the way independent random delays do. Slots are shuffled so no chat is always first.
+fn send_delays(count: usize, window: Duration) -> Vec<Duration> {
+    if window.is_zero() || count == 0 {
+        return vec![Duration::ZERO; count];
+    }
+    let slot = window / count as u32;
+    let mut rng = rand::thread_rng();
+    let mut delays: Vec<Duration> = (0..count as u32)
+        .map(|i| slot * i + if slot.is_zero() { Duration::ZERO } else { rng.gen_range(Duration::ZERO..slot) })
+        .collect();
+    delays.shuffle(&mut rng);
+    delays
+}
+
+// Current wall-clock time in the chat's timezone, falling back to the server timezone
+fn local_now(now: DateTime<Utc>, timezone: Option<Tz>) -> NaiveDateTime {
+    match timezone {
+        Some(tz) => now.with_timezone(&tz).naive_local(),
+        None => now.with_timezone(&Local).naive_local(),
+    }
+}
+
+// Outgoing messages per second, a bit under Telegram's global limit of 30
+const MESSAGES_PER_SECOND: u32 = 25;
+
+// How many times a request is retried after Telegram asks the bot to slow down
+const FLOOD_RETRIES: u32 = 3;
+
+// Send a request once the rate limiter allows it, sleeping and retrying when Telegram returns RetryAfter
+async fn send_with_flood_control<R>(app: &App, chat_id: ChatId, request: R) -> Result<Output<R>, RequestError>
+where
+    R: Request<Err = RequestError>,
+{
+    let mut attempt = 0;
+    loop {
+        app.rate_limiter.acquire().await;
+        match request.send_ref().await {
+            Err(RequestError::RetryAfter(retry_after)) if attempt < FLOOD_RETRIES => {
+                attempt += 1;
+                warn!(
+                    %chat_id,
+                    attempt,
+                    retry_after_secs = retry_after.as_secs(),
+                    "Hit Telegram's flood limit, waiting before retrying."
+                );
+                sleep(retry_after).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+// Send an announcement to every subscribed chat
+#[instrument(name = "broadcast", skip_all)]
+async fn broadcast(app: &App, text: &str) -> SendSummary {
+    let mut summary = SendSummary::default();
+    for (chat_id, settings) in app.subscriptions.snapshot().await {
+        if skip_for_dry_run(app, chat_id, text) {
+            summary.sent += 1;
+            continue;
+        }
+        let request = in_thread(app.bot.send_message(chat_id, text), &settings);
+        let sent = send_with_flood_control(app, chat_id, request).await;
+        summary.record(match check_sent(app, chat_id, sent).await {
+            Ok(_) => SendOutcome::Sent,
+            Err(attempt) => attempt.outcome(),
+        });
+    }
+    info!(%summary, "Broadcast sent.");
+    summary
+}
+
+// Remind the given chats that nobody solved today's problem yet, at most once a day per chat
+#[instrument(name = "reminder_run", skip_all, fields(chats = chats.len()))]
+async fn send_reminders(app: Arc<App>, chats: Vec<(ChatId, ChatSettings)>) {
+    // Marking every reminder as sent first keeps the next scheduler tick from sending them again,
+    // and the fresh settings catch chats that solved, turned it off or unsubscribed in the meantime
+    let mut due = Vec::new();
+    for (chat_id, settings) in chats {
+        let today = local_now(Utc::now(), settings.timezone).date();
+        let still_due = app
+            .subscriptions
+            .update(chat_id, |settings| {
+                let due = settings.reminder && !settings.solved_on(today);
+                settings.last_reminder_date = Some(today);
+                due
+            })
+            .await;
+        if still_due == Some(true) {
+            due.push((chat_id, settings));
+        }
+    }
+    for (chat_id, settings) in due {
+        let text = i18n::text(settings.lang, Key::Reminder);
+        if skip_for_dry_run(&app, chat_id, text) {
+            continue;
+        }
+        let request = in_thread(app.bot.send_message(chat_id, text), &settings);
+        match send_with_flood_control(&app, chat_id, request).await {
+            Ok(_) => info!(%chat_id, "Solve reminder sent to chat."),
+            Err(err) => error!(%chat_id, error = ?err, "Failed to send solve reminder to chat."),
+        }
+    }
+}
+
+// Send the recap of the past week's daily questions to the given chats
+#[instrument(name = "weekly_run", skip_all, fields(chats = chats.len()))]
+async fn send_weekly_digest(
+    app: Arc<App>,
+    chats: Vec<(ChatId, ChatSettings)>,
+) -> Result<(), BotError> {
+    let since = Utc::now().date_naive() - chrono::Duration::days(6);
+    let dailies = app.storage.dailies_since(since).await.map_err(BotError::Storage)?;
+    let text = format_weekly_digest(&dailies);
+    for (chat_id, settings) in chats {
+        if skip_for_dry_run(&app, chat_id, &text) {
+            continue;
+        }
+        let request = in_thread(
+            app.bot.send_message(chat_id, text.clone())
+                .parse_mode(ParseMode::Html)
+                .disable_web_page_preview(true),
+            &settings,
+        );
+        match send_with_flood_control(&app, chat_id, request).await {
+            Ok(_) => info!(%chat_id, "Weekly recap sent to chat."),
+            Err(err) => error!(%chat_id, error = ?err, "Failed to send weekly recap to chat."),
+        }
+    }
+    Ok(())
+}
+
+// Instant at which the local clock in the timezone shows `local`
+fn resolve_local<T: TimeZone>(timezone: &T, local: NaiveDateTime) -> DateTime<Utc> {
+    match timezone.from_local_datetime(&local) {
+        LocalResult::Single(time) => time.with_timezone(&Utc),
+        // The hour repeated when the clocks go back counts once, the first time
+        LocalResult::Ambiguous(earliest, _) => earliest.with_timezone(&Utc),
+        // A time skipped when the clocks go forward happens as soon as the clocks jumped past it
+        LocalResult::None => (1..=180)
+            .find_map(|minutes| timezone.from_local_datetime(&(local + chrono::Duration::minutes(minutes))).earliest())
+            .map_or_else(|| local.and_utc(), |time| time.with_timezone(&Utc)),
+    }
+}
+
+// When the trigger time of the given local date happens in the chat's timezone
+fn trigger_instant(date: NaiveDate, trigger_time: NaiveTime, timezone: Option<Tz>) -> DateTime<Utc> {
+    let local = date.and_time(trigger_time);
+    match timezone {
+        Some(tz) => resolve_local(&tz, local),
+        None => resolve_local(&Local, local),
+    }
+}
+
+// Local dates whose trigger time in the chat's timezone falls within (since, now]
+fn passed_triggers(
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+    trigger_time: NaiveTime,
+    timezone: Option<Tz>,
+) -> impl Iterator<Item = NaiveDate> {
+    // Compared as instants rather than local times, which repeat or skip an hour when the clocks change
+    let first = local_now(since, timezone).date().min(local_now(now, timezone).date());
+    let last = local_now(now, timezone).date().max(local_now(since, timezone).date());
+    first
+        .pred_opt()
+        .unwrap_or(first)
+        .iter_days()
+        .take_while(move |date| *date <= last)
+        .filter(move |date| {
+            let trigger = trigger_instant(*date, trigger_time, timezone);
+            since < trigger && trigger <= now
+        })
+}
+
+// Check whether the trigger time passed in the chat's timezone during (since, now]
+fn trigger_passed(since: DateTime<Utc>, now: DateTime<Utc>, trigger_time: NaiveTime, timezone: Option<Tz>) -> bool {
+    passed_triggers(since, now, trigger_time, timezone).next().is_some()
+}
+
+// Like `trigger_passed`, but only counting triggers on the given weekday
+fn weekly_trigger_passed(
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+    weekday: Weekday,
+    trigger_time: NaiveTime,
+    timezone: Option<Tz>,
+) -> bool {
+    passed_triggers(since, now, trigger_time, timezone).any(|trigger| trigger.weekday() == weekday)
+}
+
+// Calculate the duration from `now` until the next trigger time in the chat's timezone, accounting for clock changes
+fn duration_until_next_trigger(now: DateTime<Utc>, trigger_time: NaiveTime, timezone: Option<Tz>) -> chrono::Duration {
+    let today = local_now(now, timezone).date();
+    // A trigger that is exactly now is scheduled for tomorrow
+    let next_trigger = today
+        .iter_days()
+        .take(3)
+        .map(|date| trigger_instant(date, trigger_time, timezone))
+        .find(|trigger| *trigger > now);
+
+    let duration = next_trigger.map_or(chrono::Duration::zero(), |trigger| trigger - now);
+    debug!(%duration, "Duration until next trigger.");
+    duration
+}
+
+// When the daily is next sent to the chat, skipping the weekdays it's off and the days it's snoozed
+fn next_send(now: DateTime<Utc>, settings: &ChatSettings, default_trigger_time: NaiveTime) -> Option<DateTime<Utc>> {
+    let trigger_time = settings.trigger_time(default_trigger_time);
+    local_now(now, settings.timezone)
+        .date()
+        .iter_days()
+        .take(MAX_SNOOZE_DAYS as usize + 8)
+        .filter(|date| settings.delivers_on(*date) && settings.snooze_days_left(*date) == 0)
+        .map(|date| trigger_instant(date, trigger_time, settings.timezone))
+        .find(|trigger| *trigger > now)
+}
+
+// Build the /next reply
+fn format_next_send(now: DateTime<Utc>, settings: &ChatSettings, default_trigger_time: NaiveTime) -> String {
+    let Some(at) = next_send(now, settings, default_trigger_time) else {
+        return "No daily challenge is scheduled, check /days and /snooze.".to_string();
+    };
+    let local = local_now(at, settings.timezone).format("%a %Y-%m-%d %H:%M");
+    if settings.snooze_days_left(local_now(now, settings.timezone).date()) > 0 {
+        format!("Snoozed, daily challenges resume on {}, in {}. Use /snooze off to resume now.", local, format_duration(at - now))
+    } else {
+        format!("Next daily challenge in {} ({}).", format_duration(at - now), local)
+    }
+}
+
+// Format a duration as "5h 3m" for replies, negative durations as zero
+fn format_duration(duration: chrono::Duration) -> String {
+    let minutes = duration.num_minutes().max(0);
+    format!("{}h {}m", minutes / 60, minutes % 60)
+}
+
+// Record that the user solved today's problem in the chat and describe their streak
+async fn mark_solved(app: &App, chat_id: ChatId, user_id: UserId, lang: Lang) -> String {
+    app.subscriptions
+        .update(chat_id, |settings| {
+            let today = local_now(Utc::now(), settings.timezone).date();
+            match settings.record_solved(user_id, today) {
+                (count, true) => {
+                    info!(%chat_id, %user_id, count, "User solved today's problem.");
+                    format!("Nice work! 🔥 Streak: {} {}", count, days(count))
+                }
+                (count, false) => format!("You already marked today's problem as solved. 🔥 Streak: {} {}", count, days(count)),
+            }
+        })
+        .await
+        .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string())
+}
+
+// Pick a random problem from the index and fetch its details; until the index is loaded, LeetCode is asked directly
+async fn random_question(
+    app: &App,
+    filter: &ProblemFilter,
+) -> Result<Option<DailyQuestion>, BotError> {
+    match app.problem_index.pick(filter).await {
+        Some(Some(problem)) => fetch_question_by_slug(&app.client, &problem.slug).await,
+        Some(None) => Ok(None),
+        None => fetch_random_question(&app.client, filter).await,
+    }
+}
+
+// Sends to the subscribed Telegram chats whose delivery time passed, each in its own timezone
+struct TelegramNotifier {
+    app: Arc<App>,
+}
+
+impl TelegramNotifier {
+    // Subscribed chats due for the trigger, skipping those that are snoozed or off that weekday
+    async fn due_chats(&self, trigger: Trigger) -> Vec<(ChatId, ChatSettings)> {
+        let app = &self.app;
+        app.subscriptions
+            .snapshot()
+            .await
+            .into_iter()
+            .filter(|(_, settings)| match trigger {
+                Trigger::Passed { since, now } => {
+                    let today = local_now(now, settings.timezone).date();
+                    settings.snooze_days_left(today) == 0
+                        && settings.delivers_on(today)
+                        && trigger_passed(since, now, settings.trigger_time(app.trigger_time), settings.timezone)
+                }
+                Trigger::Startup { at } => {
+                    let local_time = local_now(at, settings.timezone);
+                    settings.snooze_days_left(local_time.date()) == 0
+                        && settings.delivers_on(local_time.date())
+                        && settings.missed_broadcast(local_time, app.trigger_time)
+                }
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Notifier for TelegramNotifier {
+    fn name(&self) -> &str {
+        "Telegram"
+    }
+
+    async fn is_due(&self, trigger: Trigger) -> bool {
+        !self.due_chats(trigger).await.is_empty()
+    }
+
+    async fn send_daily(&self, challenge: &Challenge, trigger: Trigger) -> Result<(), BotError> {
+        let app = Arc::clone(&self.app);
+        let chat_ids = self.due_chats(trigger).await;
+        if chat_ids.is_empty() {
+            return Ok(());
+        }
+        let summary = send_daily_challenge(Arc::clone(&app), chat_ids, app.send_jitter, challenge).await;
+        app.health.record_send(Utc::now());
+        report_run(&app, &challenge.question, summary).await;
+        Ok(())
+    }
+}
+
+// Wait before trying a notifier again that failed for a reason that may pass
+const NOTIFIER_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+// Resolve the override or fetch the daily once, then send the challenge through every due notifier at the same time
+async fn run_notifiers(app: Arc<App>, notifiers: Vec<Arc<dyn Notifier>>, trigger: Trigger) {
+    // The featured problem needs no daily, so a LeetCode outage only holds up runs without one
+    let challenge = match featured_problem(&app, Utc::now().date_naive(), true).await {
+        Some(question) => Ok(Some(Challenge { question, featured: true })),
+        None if !wait_for_daily_question(&app, DAILY_RETRY_INTERVAL).await => return,
+        None => daily_challenge(&app).await,
+    };
+    let challenge = match challenge {
+        Ok(Some(challenge)) => Arc::new(challenge),
+        Ok(None) => return,
+        Err(err) => {
+            error!(error = ?err, "Error resolving today's challenge.");
+            post_to_log_channel(&app, format!("Daily run failed: {}", err)).await;
+            return;
+        }
+    };
+    let mut tasks = JoinSet::new();
+    for notifier in notifiers {
+        let app = Arc::clone(&app);
+        let challenge = Arc::clone(&challenge);
+        tasks.spawn(
+            async move {
+                let mut result = notifier.send_daily(&challenge, trigger).await;
+                if let Err(err) = result.as_ref() {
+                    if !err.is_transient() {
+                        // Nothing changes by trying again, e.g. a webhook that was deleted
+                        return report_notifier_error(&app, notifier.name(), err).await;
+                    }
+                    warn!(
+                        notifier = notifier.name(),
+                        error = ?err,
+                        delay_secs = NOTIFIER_RETRY_DELAY.as_secs(),
+                        "Error sending the daily challenge, retrying."
+                    );
+                    sleep(NOTIFIER_RETRY_DELAY).await;
+                    result = notifier.send_daily(&challenge, trigger).await;
+                }
+                match result {
+                    Ok(()) => info!(notifier = notifier.name(), "Daily challenge sent."),
+                    Err(err) => report_notifier_error(&app, notifier.name(), &err).await,
+                }
+            }
+            .in_current_span(),
+        );
+    }
+    while let Some(result) = tasks.join_next().await {
+        if let Err(err) = result {
+            error!(error = ?err, "Notifier task panicked.");
+        }
+    }
+}
+
+async fn report_notifier_error(app: &App, name: &str, err: &BotError) {
+    error!(notifier = name, error = ?err, "Error sending the daily challenge.");
+    post_to_log_channel(app, format!("Error sending the daily to {}: {}", name, err)).await;
+}
+
+// Notifiers of the list that have something due for the trigger
+async fn due_notifiers(notifiers: &[Arc<dyn Notifier>], trigger: Trigger) -> Vec<Arc<dyn Notifier>> {
+    let mut due = Vec::new();
+    for notifier in notifiers {
+        if notifier.is_due(trigger).await {
+            due.push(Arc::clone(notifier));
+        }
+    }
+    due
+}
+
+// Fetch the problem index again, reporting failures to the log channel
+async fn refresh_problem_index(app: &App) -> Result<usize, BotError> {
+    let result = app.problem_index.refresh(&app.client).await;
+    if let Err(err) = &result {
+        error!(error = ?err, "Error fetching the problem index, random picks keep using the previous one.");
+        post_to_log_channel(app, format!("Error fetching the problem index: {}", err)).await;
+    }
+    result
+}
+
+// Fetch a random problem of one of the difficulties, any if empty, and describe it
+async fn random_problem_reply(app: &App, chat_id: ChatId, lang: Lang, difficulties: BTreeSet<Difficulty>) -> String {
+    info!(%chat_id, ?difficulties, "Chat requested a random problem.");
+    let filter = ProblemFilter {
+        difficulties,
+        include_paid: app.subscriptions.get(chat_id).await.is_some_and(|settings| settings.show_premium),
+        ..ProblemFilter::default()
+    };
+    match random_question(app, &filter).await {
+        Ok(Some(question)) => format!("{}\n\n{}", i18n::text(lang, Key::RandomHeader), format_question(&question)),
+        Ok(None) => "LeetCode returned no free problems for this difficulty. Try another one.".to_string(),
+        Err(err) => {
+            error!(%chat_id, error = ?err, "Error fetching random question.");
+            i18n::text(lang, Key::LeetCodeUnreachable).to_string()
+        }
+    }
+}
+
+// Handle a press of one of the buttons under a challenge message
+async fn handle_callback(bot: Bot, query: CallbackQuery, app: Arc<App>) -> ResponseResult<()> {
+    let Some(message) = &query.message else {
+        bot.answer_callback_query(query.id).send().await?;
+        return respond(());
+    };
+    let chat_id = message.chat.id;
+    let lang = app.subscriptions.get(chat_id).await.map(|settings| settings.lang).unwrap_or_default();
+    match query.data.as_deref() {
+        Some(CALLBACK_SOLVED) => {
+            // The streak is shown only to the user who pressed the button
+            let reply = mark_solved(&app, chat_id, query.from.id, lang).await;
+            bot.answer_callback_query(query.id).text(reply).send().await?;
+        }
+        Some(CALLBACK_RANDOM) => {
+            // Answered right away so the button stops spinning while LeetCode is asked
+            bot.answer_callback_query(query.id.clone()).send().await?;
+            let reply = random_problem_reply(&app, chat_id, lang, BTreeSet::new()).await;
+            let request = bot.send_message(chat_id, reply).parse_mode(ParseMode::Html).disable_web_page_preview(true);
+            let request = match topic_thread_id(message) {
+                Some(thread_id) => request.message_thread_id(thread_id),
+                None => request,
+            };
+            request.send().await?;
+        }
+        _ => {
+            warn!(%chat_id, data = ?query.data, "Unknown button pressed.");
+            bot.answer_callback_query(query.id).send().await?;
+        }
+    }
+    respond(())
+}
+
+// Handle an incoming message, dispatching on its command
+async fn handle_message(bot: Bot, message: Message, app: Arc<App>) -> ResponseResult<()> {
+    let chat_id = message.chat.id;
+    let user_id = message.from().map(|user| user.id);
+    // Files sent with a command, e.g. for /import, carry it in the caption
+    let text = message.text().or_else(|| message.caption()).unwrap_or_default();
+    let (command, argument) = match text.split_once(char::is_whitespace) {
+        Some((command, argument)) => (command, argument.trim()),
+        None => (text, ""),
+    };
+    // Commands in groups may be addressed as /command@bot_name
+    let command = command.split('@').next().unwrap_or_default();
+    // Replies use the chat's language, English until it subscribes and picks one
+    let lang = app.subscriptions.get(chat_id).await.map(|settings| settings.lang).unwrap_or_default();
+    match command {
+        "/start" => {
+            // Admins can always subscribe, even when the bot is full
+            let bypass_cap = app.admin_chat_id == Some(chat_id);
+            let result = app.subscriptions.subscribe(chat_id, bypass_cap).await;
+            if let SubscribeResult::Waitlisted { position } = result {
+                info!(%chat_id, position, "Bot is at capacity, chat added to the waitlist.");
+                let reply = format!(
+                    "The bot is at capacity right now. You are number {} on the waitlist and will be subscribed automatically once a spot opens up. Send /stop to leave the waitlist.",
+                    position
+                );
+                bot.send_message(chat_id, reply).send().await?;
+                return Ok(());
+            }
+            // Dailies go to the topic /start was sent in, so it can be moved by sending /start in another one
+            let thread_id = topic_thread_id(&message);
+            let (settings, moved) = app
+                .subscriptions
+                .update(chat_id, |settings| {
+                    let moved = settings.thread_id != thread_id;
+                    settings.thread_id = thread_id;
+                    (settings.clone(), moved)
+                })
+                .await
+                .unwrap_or_default();
+            if result == SubscribeResult::AlreadySubscribed {
+                info!(%chat_id, moved, "Chat sent /start again.");
+                let mut reply = i18n::text(lang, Key::AlreadySubscribed).to_string();
+                if moved {
+                    reply.push_str("\nDaily challenges will be sent to this topic from now on.");
+                }
+                in_thread(bot.send_message(chat_id, reply), &settings).send().await?;
+                return Ok(());
+            }
+            info!(%chat_id, "Chat started receiving challenges.");
+            in_thread(bot.send_message(chat_id, i18n::text(lang, Key::Started)), &settings)
+                .send()
+                .await?;
+
+            // Send today's challenge right away, only to the chat that subscribed
+            if !app.quiet_start {
+                match todays_challenge(&app, false).await {
+                    Ok(Some(challenge)) => {
+                        send_daily_challenge(Arc::clone(&app), vec![(chat_id, settings)], Duration::ZERO, &challenge).await;
+                    }
+                    Ok(None) => {}
+                    Err(err) => error!(%chat_id, error = ?err, "Error sending initial challenge."),
+                }
+            }
+        }
+        "/stop" => {
+            info!(%chat_id, "Chat stopped receiving challenges.");
+            let result = app.subscriptions.unsubscribe(chat_id).await;
+            welcome_from_waitlist(&app, result.promoted).await;
+            bot.send_message(chat_id, i18n::text(lang, Key::Stopped))
+                .send()
+                .await?;
+        }
+        "/help" => {
+            bot.send_message(chat_id, help_text())
+                .parse_mode(ParseMode::Html)
+                .send()
+                .await?;
+        }
+        "/solved" => {
+            let reply = match user_id {
+                Some(user_id) => mark_solved(&app, chat_id, user_id, lang).await,
+                None => "Only users can mark problems as solved.".to_string(),
+            };
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/streak" => {
+            let reply = match (app.subscriptions.get(chat_id).await, user_id) {
+                (Some(settings), Some(user_id)) => {
+                    let today = local_now(Utc::now(), settings.timezone).date();
+                    let count = settings.streaks.get(&user_id).map_or(0, |streak| streak.current(today));
+                    format!("🔥 Streak: {} {}. Send /solved after finishing the daily problem.", count, days(count))
+                }
+                (Some(_), None) => "Only users have streaks.".to_string(),
+                (None, _) => "Subscribe with /start to track streaks.".to_string(),
+            };
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/profile" => {
+            let username = if argument.is_empty() {
+                let settings = app.subscriptions.get(chat_id).await;
+                user_id.and_then(|user_id| settings.and_then(|settings| settings.leetcode_users.get(&user_id).cloned()))
+            } else {
+                Some(argument.to_string())
+            };
+            let reply = match username {
+                Some(username) if valid_username(&username) => match cached_profile(&app, &username).await {
+                    Ok(Some(profile)) => format_profile(&profile),
+                    Ok(None) => format!("There's no LeetCode user named {}.", html::escape(&username)),
+                    Err(err) => {
+                        error!(username, error = ?err, "Error fetching LeetCode profile.");
+                        html::escape(i18n::text(lang, Key::LeetCodeUnreachable))
+                    }
+                },
+                Some(username) => format!("\"{}\" isn't a valid LeetCode username.", html::escape(&username)),
+                None => "Usage: /profile <username>, or link yours with /setuser <username> first.".to_string(),
+            };
+            bot.send_message(chat_id, reply).parse_mode(ParseMode::Html).send().await?;
+        }
+        "/setuser" => {
+            let Some(user_id) = user_id else {
+                bot.send_message(chat_id, "Only users can link a LeetCode username.").send().await?;
+                return respond(());
+            };
+            if argument.is_empty() || !(argument.eq_ignore_ascii_case("off") || valid_username(argument)) {
+                bot.send_message(chat_id, "Usage: /setuser <username> to link your LeetCode username, /setuser off to unlink it.")
+                    .send()
+                    .await?;
+                return respond(());
+            }
+            // Check the user exists before binding, so a typo shows up now rather than in every /profile
+            let username = if argument.eq_ignore_ascii_case("off") {
+                None
+            } else {
+                match cached_profile(&app, argument).await {
+                    Ok(Some(profile)) => Some(profile.username),
+                    Ok(None) => {
+                        bot.send_message(chat_id, format!("There's no LeetCode user named {}.", argument)).send().await?;
+                        return respond(());
+                    }
+                    Err(err) => {
+                        error!(username = argument, error = ?err, "Error fetching LeetCode profile.");
+                        bot.send_message(chat_id, i18n::text(lang, Key::LeetCodeUnreachable)).send().await?;
+                        return respond(());
+                    }
+                }
+            };
+            let reply = app.subscriptions.update(chat_id, |settings| match username {
+                Some(username) => {
+                    info!(%chat_id, %user_id, username, "User linked a LeetCode username.");
+                    let reply = format!("Linked LeetCode user {}, /profile now shows it.", username);
+                    settings.leetcode_users.insert(user_id, username);
+                    reply
+                }
+                None => {
+                    info!(%chat_id, %user_id, "User unlinked their LeetCode username.");
+                    settings.leetcode_users.remove(&user_id);
+                    "Your LeetCode username is no longer linked.".to_string()
+                }
+            })
+            .await
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/leaderboard" => {
+            let Some(settings) = app.subscriptions.get(chat_id).await else {
+                bot.send_message(chat_id, i18n::text(lang, Key::NotSubscribed)).send().await?;
+                return respond(());
+            };
+            if settings.leetcode_users.is_empty() {
+                bot.send_message(chat_id, "Nobody linked a LeetCode username yet, use /setuser <username> first.").send().await?;
+                return respond(());
+            }
+            // A member whose profile can't be fetched is listed as missing instead of failing the whole board
+            let mut profiles = Vec::new();
+            let mut missing = Vec::new();
+            for username in BTreeSet::from_iter(settings.leetcode_users.into_values()) {
+                match cached_profile(&app, &username).await {
+                    Ok(Some(profile)) => profiles.push(profile),
+                    Ok(None) => missing.push(username),
+                    Err(err) => {
+                        warn!(username, error = ?err, "Error fetching LeetCode profile for the leaderboard.");
+                        missing.push(username);
+                    }
+                }
+            }
+            bot.send_message(chat_id, format_leaderboard(profiles, &missing)).parse_mode(ParseMode::Html).send().await?;
+        }
+        "/next" => {
+            let reply = match app.subscriptions.get(chat_id).await {
+                _ if app.paused.load(Ordering::Relaxed) => "Scheduled sends are paused by the admin for now.".to_string(),
+                Some(settings) => format_next_send(Utc::now(), &settings, app.trigger_time),
+                None => i18n::text(lang, Key::NotSubscribed).to_string(),
+            };
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/status" => {
+            let settings = app.subscriptions.get(chat_id).await;
+            let mut text = format_status(settings.as_ref(), app.trigger_time, app.send_jitter);
+            if app.admin_chat_id == Some(chat_id) {
+                let paused = app.paused.load(Ordering::Relaxed);
+                text.push_str(&format!("\nScheduled sends: {}", if paused { "paused, /resume to restart" } else { "running" }));
+                let index = match app.problem_index.status().await {
+                    Some((count, fetched_at)) => format!("{} problems, fetched {}", count, fetched_at.format("%Y-%m-%d %H:%M UTC")),
+                    None => "not loaded, random picks ask LeetCode directly".to_string(),
+                };
+                text.push_str(&format!("\nProblem index: {}", index));
+            }
+            bot.send_message(chat_id, text).parse_mode(ParseMode::Html).send().await?;
+        }
+        "/resend" => {
+            let Some(settings) = app.subscriptions.get(chat_id).await else {
+                bot.send_message(chat_id, i18n::text(lang, Key::NotSubscribed)).send().await?;
+                return respond(());
+            };
+            info!(%chat_id, "Chat requested a resend of today's challenge.");
+            // Same path as the scheduled send, so pinning and the broadcast date behave the same
+            let result = match todays_challenge(&app, false).await {
+                Ok(Some(challenge)) => {
+                    Ok(send_daily_challenge(Arc::clone(&app), vec![(chat_id, settings)], Duration::ZERO, &challenge).await)
+                }
+                Ok(None) => Ok(SendSummary::default()),
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(summary) if summary.sent > 0 => {}
+                Ok(_) => {
+                    bot.send_message(chat_id, "Sorry, I couldn't resend today's challenge. Please try again later.")
+                        .send()
+                        .await?;
+                }
+                Err(err) => {
+                    error!(%chat_id, error = ?err, "Error resending daily challenge.");
+                    bot.send_message(chat_id, i18n::text(lang, Key::LeetCodeUnreachable)).send().await?;
+                }
+            }
+        }
+        "/today" => {
+            info!(%chat_id, "Chat requested today's challenge.");
+            let settings = app.subscriptions.get(chat_id).await.unwrap_or_default();
+            let reply = match cached_daily_question(&app).await {
+                Ok(Some(daily_question)) => {
+                    let mut text = format_daily_message(lang, Key::DailyLabel, Some(&daily_question));
+                    if settings.preview {
+                        text.push_str(&format_preview(&daily_question, app.preview_chars).unwrap_or_default());
+                    }
+                    if settings.hints {
+                        text.push_str(&format_hints(&daily_question).unwrap_or_default());
+                    }
+                    text.push_str(&format_snippet(&daily_question, settings.code_lang.as_deref()).unwrap_or_default());
+                    // Same path as the scheduled send, so long messages are split and forum topics are kept
+                    let text = with_footer(text, app.message_footer.as_deref());
+                    let keyboard = daily_keyboard(lang, Some(&daily_question.link));
+                    send_long_message(&app, chat_id, &settings, &text, &keyboard, None).await?;
+                    return respond(());
+                }
+                Ok(None) => i18n::text(lang, Key::TodayUnavailable),
+                Err(err) => {
+                    error!(%chat_id, error = ?err, "Error fetching daily question.");
+                    i18n::text(lang, Key::LeetCodeUnreachable)
+                }
+            };
+            in_thread(bot.send_message(chat_id, reply), &settings).send().await?;
+        }
+        "/random" => {
+            let difficulties = if argument.is_empty() {
+                Some(BTreeSet::new())
+            } else {
+                Difficulty::parse(argument).map(|difficulty| BTreeSet::from([difficulty]))
+            };
+            let reply = match difficulties {
+                Some(difficulties) => random_problem_reply(&app, chat_id, lang, difficulties).await,
+                None => format!(
+                    "Unknown difficulty \"{}\". Use /random easy, /random medium or /random hard.",
+                    html::escape(argument)
+                ),
+            };
+            bot.send_message(chat_id, reply)
+                .parse_mode(ParseMode::Html)
+                .disable_web_page_preview(true)
+                .send()
+                .await?;
+        }
+        "/problem" => {
+            if argument.is_empty() {
+                bot.send_message(chat_id, "Usage: /problem <slug|number>, e.g. /problem two-sum or /problem 1").send().await?;
+                return respond(());
+            }
+            info!(%chat_id, problem = argument, "Chat requested a problem.");
+            let result = match argument.parse::<u32>() {
+                Ok(number) => fetch_question_by_number(&app.client, number).await,
+                Err(_) => fetch_question_by_slug(&app.client, &problem_slug(argument)).await,
+            };
+            let reply = match result {
+                Ok(Some(question)) if question.paid_only => format!(
+                    "{}\n\nThis is a premium problem, opening it needs a LeetCode subscription.",
+                    format_question(&question)
+                ),
+                Ok(Some(question)) => format_question(&question),
+                Ok(None) => format!("No LeetCode problem \"{}\" found. Use its number or the slug from its link.", html::escape(argument)),
+                Err(err) => {
+                    error!(%chat_id, error = ?err, "Error fetching problem.");
+                    i18n::text(lang, Key::LeetCodeUnreachable).to_string()
+                }
+            };
+            bot.send_message(chat_id, reply)
+                .parse_mode(ParseMode::Html)
+                .disable_web_page_preview(true)
+                .send()
+                .await?;
+        }
+        "/contest" => {
+            info!(%chat_id, "Chat requested the next contest.");
+            let now = Utc::now();
+            let reply = match fetch_upcoming_contest(&app.client, now).await {
+                Ok(Some(contest)) => {
+                    let timezone = app.subscriptions.get(chat_id).await.and_then(|settings| settings.timezone);
+                    format_contest(&contest, now, timezone)
+                }
+                Ok(None) => "No upcoming LeetCode contest is scheduled right now.".to_string(),
+                Err(err) => {
+                    error!(%chat_id, error = ?err, "Error fetching upcoming contests.");
+                    i18n::text(lang, Key::LeetCodeUnreachable).to_string()
+                }
+            };
+            bot.send_message(chat_id, reply)
+                .parse_mode(ParseMode::Html)
+                .disable_web_page_preview(true)
+                .send()
+                .await?;
+        }
+        "/timezone" => {
+            let reply = app.subscriptions.update(chat_id, |settings| {
+                if argument.is_empty() {
+                    return match settings.timezone {
+                        Some(tz) => format!("Current timezone: {}", tz),
+                        None => "Current timezone: server default. Use /timezone Europe/Berlin to change it.".to_string(),
+                    };
+                }
+                match argument.parse::<Tz>() {
+                    Ok(tz) => {
+                        info!(%chat_id, timezone = %tz, "Chat set timezone.");
+                        settings.timezone = Some(tz);
+                        let next = duration_until_next_trigger(Utc::now(), settings.trigger_time(app.trigger_time), Some(tz));
+                        format!("Timezone set to {}. Next challenge in {}.", tz, format_duration(next))
+                    }
+                    Err(_) => format!(
+                        "Unknown timezone \"{}\". Use an IANA name like Europe/Berlin or America/New_York.",
+                        argument
+                    ),
+                }
+            })
+            .await
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/settime" => {
+            let reply = app.subscriptions.update(chat_id, |settings| {
+                if argument.is_empty() {
+                    return format!(
+                        "Current delivery time: {}{}. Use /settime HH:MM to change it.",
+                        settings.trigger_time(app.trigger_time).format("%H:%M"),
+                        if settings.trigger_time.is_none() { " (default)" } else { "" }
+                    );
+                }
+                match NaiveTime::parse_from_str(argument, "%H:%M") {
+                    Ok(time) => {
+                        info!(%chat_id, %time, "Chat set delivery time.");
+                        settings.trigger_time = Some(time);
+                        let next = duration_until_next_trigger(Utc::now(), time, settings.timezone);
+                        format!(
+                            "Delivery time set to {}. Next challenge in {}.",
+                            time.format("%H:%M"),
+                            format_duration(next)
+                        )
+                    }
+                    Err(_) => format!(
+                        "Invalid time \"{}\". Use the 24-hour format HH:MM, e.g. /settime 08:30.",
+                        argument
+                    ),
+                }
+            })
+            .await
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/days" => {
+            let reply = app.subscriptions.update(chat_id, |settings| {
+                if argument.is_empty() {
+                    return format!(
+                        "The daily challenge is sent {}. Use /days mon,tue,wed,thu,fri, /days weekdays or /days all to change it.",
+                        format_days_phrase(&settings.days)
+                    );
+                }
+                match parse_weekdays(argument) {
+                    Some(days) => {
+                        info!(%chat_id, ?days, "Chat set delivery days.");
+                        settings.days = days;
+                        format!("The daily challenge will now be sent {}.", format_days_phrase(&settings.days))
+                    }
+                    None => format!(
+                        "Invalid days \"{}\". Use a comma-separated list of mon, tue, wed, thu, fri, sat and sun, or weekdays, weekends or all.",
+                        argument
+                    ),
+                }
+            })
+            .await
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/difficulty" => {
+            let reply = app.subscriptions.update(chat_id, |settings| {
+                if argument.is_empty() {
+                    return format!(
+                        "Current difficulties: {}. Use /difficulty medium,hard or /difficulty all to change them.",
+                        format_difficulties(&settings.difficulties)
+                    );
+                }
+                match parse_difficulties(argument) {
+                    Some(difficulties) => {
+                        info!(%chat_id, ?difficulties, "Chat set difficulties.");
+                        settings.difficulties = difficulties;
+                        format!(
+                            "You will receive {} problems. When the daily doesn't match, a random problem is sent instead.",
+                            format_difficulties(&settings.difficulties)
+                        )
+                    }
+                    None => format!(
+                        "Invalid difficulties \"{}\". Use a comma-separated list of easy, medium and hard, or all.",
+                        argument
+                    ),
+                }
+            })
+            .await
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/reminder" => {
+            let reply = app.subscriptions.update(chat_id, |settings| {
+                match parse_toggle(argument) {
+                    Some(enabled) => {
+                        info!(%chat_id, enabled, "Chat set solve reminder.");
+                        settings.reminder = enabled;
+                        format!("Solve reminders are now {}.", format_toggle(enabled))
+                    }
+                    None => format!(
+                        "Solve reminders are {}. Use /reminder on or /reminder off to change it.",
+                        format_toggle(settings.reminder)
+                    ),
+                }
+            })
+            .await
+            .map(|reply| format!("{}\nThey're sent {}h after the daily if nobody sent /solved.", reply, app.reminder_delay.as_secs() / 3600))
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/hints" => {
+            let reply = app.subscriptions.update(chat_id, |settings| {
+                match parse_toggle(argument) {
+                    Some(enabled) => {
+                        info!(%chat_id, enabled, "Chat set hint count.");
+                        settings.hints = enabled;
+                        format!("The hint count is now {}.", format_toggle(enabled))
+                    }
+                    None => format!(
+                        "The hint count is {}. Use /hints on or /hints off to change it.",
+                        format_toggle(settings.hints)
+                    ),
+                }
+            })
+            .await
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/breakdown" => {
+            let reply = app.subscriptions.update(chat_id, |settings| {
+                match parse_toggle(argument) {
+                    Some(enabled) => {
+                        info!(%chat_id, enabled, "Chat set difficulty breakdown.");
+                        settings.breakdown = enabled;
+                        format!("The difficulty breakdown is now {}.", format_toggle(enabled))
+                    }
+                    None => format!(
+                        "The difficulty breakdown is {}. Use /breakdown on or /breakdown off to change it.",
+                        format_toggle(settings.breakdown)
+                    ),
+                }
+            })
+            .await
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/weekly" => {
+            let reply = app.subscriptions.update(chat_id, |settings| {
+                match parse_toggle(argument) {
+                    Some(enabled) => {
+                        info!(%chat_id, enabled, "Chat set weekly recap.");
+                        settings.weekly_digest = enabled;
+                        format!("Weekly recap is now {}.", format_toggle(enabled))
+                    }
+                    None => format!(
+                        "Weekly recap is {}. Use /weekly on or /weekly off to change it.",
+                        format_toggle(settings.weekly_digest)
+                    ),
+                }
+            })
+            .await
+            .map(|reply| {
+                format!(
+                    "{}\nIt's sent every {} at {}.",
+                    reply,
+                    app.weekly_day,
+                    app.weekly_time.format("%H:%M")
+                )
+            })
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/list" => {
+            if app.admin_chat_id != Some(chat_id) {
+                warn!(%chat_id, "Unauthorized /list.");
+                bot.send_message(chat_id, NOT_AUTHORIZED).send().await?;
+                return respond(());
+            }
+            let mut chat_ids: Vec<ChatId> = app.subscriptions.snapshot().await.into_iter().map(|(id, _)| id).collect();
+            chat_ids.sort_by_key(|chat_id| chat_id.0);
+            let mut reply = format!("{} subscribed {}", chat_ids.len(), if chat_ids.len() == 1 { "chat" } else { "chats" });
+            for chat_id in chat_ids {
+                reply.push_str(&format!("\n{}", chat_id));
+            }
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/version" => {
+            bot.send_message(chat_id, format_version()).send().await?;
+        }
+        "/count" => {
+            let (chats, timezones) = app.subscriptions.counts().await;
+            let subscribed = app.subscriptions.get(chat_id).await.is_some();
+            bot.send_message(chat_id, format_count(subscribed, chats, timezones)).send().await?;
+        }
+        "/stats" => {
+            if app.admin_chat_id != Some(chat_id) {
+                warn!(%chat_id, "Unauthorized /stats.");
+                bot.send_message(chat_id, NOT_AUTHORIZED).send().await?;
+                return respond(());
+            }
+            let reply = format_stats(&app).await;
+            bot.send_message(chat_id, reply).parse_mode(ParseMode::Html).send().await?;
+        }
+        "/feedback" => {
+            let Some(admin_chat_id) = app.admin_chat_id else {
+                bot.send_message(chat_id, "Feedback isn't enabled for this bot.").send().await?;
+                return respond(());
+            };
+            if argument.is_empty() {
+                bot.send_message(chat_id, "Usage: /feedback <your message>").send().await?;
+                return respond(());
+            }
+            {
+                let mut last_feedback = app.last_feedback.lock().await;
+                let now = Instant::now();
+                if let Some(wait) = last_feedback
+                    .get(&chat_id)
+                    .map(|sent| FEEDBACK_INTERVAL.saturating_sub(now.duration_since(*sent)))
+                    .filter(|wait| !wait.is_zero())
+                {
+                    let reply = format!("Please wait {}s before sending more feedback.", wait.as_secs().max(1));
+                    bot.send_message(chat_id, reply).send().await?;
+                    return respond(());
+                }
+                last_feedback.insert(chat_id, now);
+            }
+            info!(%chat_id, "Chat sent feedback.");
+            let sender = match message.from() {
+                Some(user) => match &user.username {
+                    Some(username) => format!("@{}", username),
+                    None => user.full_name(),
+                },
+                None => "unknown sender".to_string(),
+            };
+            let text = format!(
+                "<b>Feedback</b> from chat <code>{}</code> ({})\n{}",
+                chat_id,
+                html::escape(&sender),
+                html::escape(argument)
+            );
+            let reply = match bot.send_message(admin_chat_id, text).parse_mode(ParseMode::Html).send().await {
+                Ok(_) => "Thanks, your feedback was sent to the bot's admin.",
+                Err(err) => {
+                    error!(%chat_id, error = ?err, "Failed to forward feedback to the admin.");
+                    "Sorry, your feedback couldn't be delivered. Please try again later."
+                }
+            };
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/export" => {
+            if app.admin_chat_id != Some(chat_id) {
+                warn!(%chat_id, "Unauthorized /export.");
+                bot.send_message(chat_id, NOT_AUTHORIZED).send().await?;
+                return respond(());
+            }
+            let dump = app.subscriptions.dump().await;
+            info!(%chat_id, chats = dump.chats.len(), waitlisted = dump.waitlist.len(), "Admin exported subscriptions.");
+            let data = match serde_json::to_vec_pretty(&dump) {
+                Ok(data) => data,
+                Err(err) => {
+                    error!(error = %err, "Failed to serialize subscriptions.");
+                    bot.send_message(chat_id, "Sorry, the subscriptions couldn't be exported.").send().await?;
+                    return respond(());
+                }
+            };
+            let file_name = format!("subscriptions-{}.json", Utc::now().format("%Y-%m-%d"));
+            bot.send_document(chat_id, InputFile::memory(data).file_name(file_name))
+                .caption(format!("{} subscribed chats, {} waitlisted.", dump.chats.len(), dump.waitlist.len()))
+                .send()
+                .await?;
+        }
+        "/import" => {
+            if app.admin_chat_id != Some(chat_id) {
+                warn!(%chat_id, "Unauthorized /import.");
+                bot.send_message(chat_id, NOT_AUTHORIZED).send().await?;
+                return respond(());
+            }
+            let mode = match argument {
+                "" | "merge" => Some(ImportMode::Merge),
+                "replace" => Some(ImportMode::Replace),
+                _ => None,
+            };
+            // The export can be sent with /import as its caption, or /import can reply to it
+            let document = message.document().or_else(|| message.reply_to_message().and_then(Message::document));
+            let (Some(mode), Some(document)) = (mode, document) else {
+                bot.send_message(chat_id, IMPORT_USAGE).send().await?;
+                return respond(());
+            };
+            if document.file.size > MAX_IMPORT_BYTES {
+                bot.send_message(chat_id, "That file is too large to be a subscription export.").send().await?;
+                return respond(());
+            }
+            let data = match download_document(&bot, &document.file.id).await {
+                Ok(data) => data,
+                Err(err) => {
+                    error!(%chat_id, error = ?err, "Failed to download the import file.");
+                    bot.send_message(chat_id, "Sorry, the file couldn't be downloaded. Please try again.").send().await?;
+                    return respond(());
+                }
+            };
+            let dump = match serde_json::from_slice::<SubscriptionDump>(&data) {
+                Ok(dump) => dump,
+                Err(err) => {
+                    warn!(%chat_id, error = %err, "Rejected a malformed import file.");
+                    let reply = format!("That file isn't a subscription export from /export, nothing was imported.\n{}", err);
+                    bot.send_message(chat_id, reply).send().await?;
+                    return respond(());
+                }
+            };
+            let summary = app.subscriptions.import(dump, mode).await;
+            info!(%chat_id, ?mode, ?summary, "Admin imported subscriptions.");
+            let reply = format!(
+                "Imported {} chats and {} waitlist entries{}.",
+                summary.imported,
+                summary.waitlisted,
+                if mode == ImportMode::Replace { format!(", removed {} chats", summary.removed) } else { String::new() }
+            );
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/pause" | "/resume" => {
+            if app.admin_chat_id != Some(chat_id) {
+                warn!(%chat_id, command, "Unauthorized pause or resume.");
+                bot.send_message(chat_id, NOT_AUTHORIZED).send().await?;
+                return respond(());
+            }
+            let paused = command == "/pause";
+            // Saved first, so a restart right after the reply keeps the new state
+            app.storage.save_paused(paused).await;
+            let was_paused = app.paused.swap(paused, Ordering::Relaxed);
+            let reply = match (was_paused, paused) {
+                (false, true) => {
+                    warn!("Admin paused scheduled sends.");
+                    "Scheduled sends are paused, runs are skipped until /resume. Commands keep working."
+                }
+                (true, false) => {
+                    info!("Admin resumed scheduled sends.");
+                    "Scheduled sends are running again from the next trigger. Runs skipped while paused aren't sent."
+                }
+                (true, true) => "Scheduled sends are already paused.",
+                (false, false) => "Scheduled sends aren't paused.",
+            };
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/deadletters" => {
+            if app.admin_chat_id != Some(chat_id) {
+                warn!(%chat_id, "Unauthorized /deadletters.");
+                bot.send_message(chat_id, NOT_AUTHORIZED).send().await?;
+                return respond(());
+            }
+            let reply = if argument.eq_ignore_ascii_case("clear") {
+                let cleared = std::mem::take(&mut *app.dead_letters.lock().await).len();
+                format!("Cleared {} failed sends.", cleared)
+            } else {
+                format_dead_letters(&*app.dead_letters.lock().await)
+            };
+            bot.send_message(chat_id, reply).parse_mode(ParseMode::Html).send().await?;
+        }
+        "/refreshcache" => {
+            if app.admin_chat_id != Some(chat_id) {
+                warn!(%chat_id, "Unauthorized /refreshcache.");
+                bot.send_message(chat_id, NOT_AUTHORIZED).send().await?;
+                return respond(());
+            }
+            info!(%chat_id, "Admin refreshed the problem index.");
+            let reply = match refresh_problem_index(&app).await {
+                Ok(count) => format!("Problem index refreshed, {} problems.", count),
+                Err(err) => format!("Couldn't refresh the problem index: {}", err),
+            };
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/override" => {
+            if app.admin_chat_id != Some(chat_id) {
+                warn!(%chat_id, "Unauthorized /override.");
+                bot.send_message(chat_id, NOT_AUTHORIZED).send().await?;
+                return respond(());
+            }
+            if argument.eq_ignore_ascii_case("clear") {
+                let cleared = app.featured.lock().await.take();
+                app.storage.save_featured(None).await;
+                let reply = match cleared {
+                    Some(_) => {
+                        info!("Admin cleared the featured problem.");
+                        "Override cleared, the daily is sent as usual."
+                    }
+                    None => "No override was set.",
+                };
+                bot.send_message(chat_id, reply).send().await?;
+                return respond(());
+            }
+            if argument.is_empty() {
+                let reply = format_featured(app.featured.lock().await.as_ref());
+                bot.send_message(chat_id, reply).parse_mode(ParseMode::Html).disable_web_page_preview(true).send().await?;
+                return respond(());
+            }
+            // Checked against LeetCode now, so the scheduled send can't fail on a typo
+            let result = match argument.parse::<u32>() {
+                Ok(number) => fetch_question_by_number(&app.client, number).await,
+                Err(_) => fetch_question_by_slug(&app.client, &problem_slug(argument)).await,
+            };
+            let reply = match result {
+                Ok(Some(question)) => {
+                    info!(title = %question.title, "Admin set a featured problem.");
+                    let featured = FeaturedProblem { question, date: None };
+                    app.storage.save_featured(Some(&featured)).await;
+                    let reply = format_featured(Some(&featured));
+                    *app.featured.lock().await = Some(featured);
+                    reply
+                }
+                Ok(None) => format!("No LeetCode problem \"{}\" found. Use its number or the slug from its link.", html::escape(argument)),
+                Err(err) => {
+                    error!(error = ?err, "Error fetching the problem to feature.");
+                    i18n::text(lang, Key::LeetCodeUnreachable).to_string()
+                }
+            };
+            bot.send_message(chat_id, reply).parse_mode(ParseMode::Html).disable_web_page_preview(true).send().await?;
+        }
+        "/route" => {
+            if app.admin_chat_id != Some(chat_id) {
+                warn!(%chat_id, "Unauthorized /route.");
+                bot.send_message(chat_id, NOT_AUTHORIZED).send().await?;
+                return respond(());
+            }
+            if argument.is_empty() {
+                let reply = format_routes(&*app.routes.lock().await);
+                bot.send_message(chat_id, reply).parse_mode(ParseMode::Html).send().await?;
+                return respond(());
+            }
+            let Some((difficulty, chats)) = parse_route(argument) else {
+                bot.send_message(chat_id, "Usage: /route <easy|medium|hard> <chat IDs separated by commas|off>").send().await?;
+                return respond(());
+            };
+            let mut reply = if chats.is_empty() {
+                info!(%difficulty, "Admin removed a difficulty route.");
+                format!("{} problems are no longer routed.", difficulty)
+            } else {
+                info!(%difficulty, chats = chats.len(), "Admin routed a difficulty.");
+                format!("{} problems now go to {} {}.", difficulty, chats.len(), if chats.len() == 1 { "chat" } else { "chats" })
+            };
+            let mut unsubscribed = Vec::new();
+            for chat in &chats {
+                if app.subscriptions.get(*chat).await.is_none() {
+                    unsubscribed.push(chat.to_string());
+                }
+            }
+            if !unsubscribed.is_empty() {
+                reply.push_str(&format!(
+                    "\nNot subscribed, so nothing is sent there until /start is used: {}",
+                    unsubscribed.join(", ")
+                ));
+            }
+            let mut routes = app.routes.lock().await;
+            if chats.is_empty() {
+                routes.remove(&difficulty);
+            } else {
+                routes.insert(difficulty, chats);
+            }
+            app.storage.save_routes(&routes).await;
+            drop(routes);
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/log" => {
+            if app.admin_chat_id != Some(chat_id) {
+                warn!(%chat_id, "Unauthorized /log.");
+                bot.send_message(chat_id, NOT_AUTHORIZED).send().await?;
+                return respond(());
+            }
+            let date = if argument.is_empty() {
+                Some(Utc::now().date_naive())
+            } else {
+                NaiveDate::parse_from_str(argument, "%Y-%m-%d").ok()
+            };
+            let Some(date) = date else {
+                bot.send_message(chat_id, "Usage: /log [YYYY-MM-DD], the UTC day of the sends, today if left out").send().await?;
+                return respond(());
+            };
+            let reply = match app.storage.sends_on(date).await {
+                Ok(records) => format_send_log(date, &records),
+                Err(err) => {
+                    error!(%date, error = ?err, "Failed to read the send log.");
+                    "Sorry, the send log couldn't be read.".to_string()
+                }
+            };
+            bot.send_message(chat_id, reply).parse_mode(ParseMode::Html).send().await?;
+        }
+        "/broadcast" => {
+            if app.admin_chat_id != Some(chat_id) {
+                warn!(%chat_id, "Unauthorized /broadcast.");
+                bot.send_message(chat_id, NOT_AUTHORIZED).send().await?;
+                return respond(());
+            }
+            if argument.is_empty() {
+                bot.send_message(chat_id, "Usage: /broadcast <text>").send().await?;
+                return respond(());
+            }
+            info!(%chat_id, "Admin started a broadcast.");
+            let summary = broadcast(&app, argument).await;
+            bot.send_message(chat_id, format!("Broadcast finished: {}.", summary)).send().await?;
+        }
+        "/tags" => {
+            let reply = app.subscriptions.update(chat_id, |settings| {
+                if argument.is_empty() {
+                    return format!(
+                        "Current tags: {}. Use /tags graph,dp or /tags all to change them.",
+                        format_tags(&settings.tags)
+                    );
+                }
+                settings.tags = parse_tags(argument);
+                info!(%chat_id, tags = ?settings.tags, "Chat set tags.");
+                format!(
+                    "You will receive problems tagged {}. When the daily doesn't match, a random matching problem is sent instead.",
+                    format_tags(&settings.tags)
+                )
+            })
+            .await
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/premium" => {
+            let reply = app.subscriptions.update(chat_id, |settings| match argument.to_ascii_lowercase().as_str() {
+                "show" => {
+                    info!(%chat_id, "Chat set premium problems to show.");
+                    settings.show_premium = true;
+                    "Premium problems will be sent like any other problem.".to_string()
+                }
+                "skip" => {
+                    info!(%chat_id, "Chat set premium problems to skip.");
+                    settings.show_premium = false;
+                    "Premium problems will be replaced by a free problem.".to_string()
+                }
+                _ => format!(
+                    "Premium problems are {}. Use /premium skip or /premium show to change it.",
+                    if settings.show_premium { "shown" } else { "skipped" }
+                ),
+            })
+            .await
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/snooze" => {
+            let reply = app.subscriptions.update(chat_id, |settings| {
+                let today = local_now(Utc::now(), settings.timezone).date();
+                if argument.eq_ignore_ascii_case("off") {
+                    info!(%chat_id, "Chat cancelled snooze.");
+                    settings.snoozed_until = None;
+                    return "Snooze cancelled, daily challenges are back on.".to_string();
+                }
+                match argument.parse::<u32>() {
+                    Ok(count) if (1..=MAX_SNOOZE_DAYS).contains(&count) => {
+                        info!(%chat_id, days = count, "Chat snoozed daily challenges.");
+                        settings.snoozed_until = today.checked_add_days(chrono::Days::new(u64::from(count)));
+                        format!("Daily challenges are snoozed for {} {}. Use /snooze off to resume early.", count, days(count))
+                    }
+                    _ => match settings.snooze_days_left(today) {
+                        0 => format!("Use /snooze <days> with 1 to {} days, or /snooze off.", MAX_SNOOZE_DAYS),
+                        left => format!(
+                            "Snoozed for {} more {}. Use /snooze <days> with 1 to {} days, or /snooze off.",
+                            left,
+                            days(left),
+                            MAX_SNOOZE_DAYS
+                        ),
+                    },
+                }
+            })
+            .await
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/companies" => {
+            if !app.client.authenticated() {
+                bot.send_message(chat_id, "Company tags require premium LeetCode credentials, which this bot isn't configured with.")
+                    .send()
+                    .await?;
+                return respond(());
+            }
+            let reply = app.subscriptions.update(chat_id, |settings| match parse_toggle(argument) {
+                Some(enabled) => {
+                    info!(%chat_id, enabled, "Chat set company tags.");
+                    settings.companies = enabled;
+                    format!("Company tags are now {}.", format_toggle(enabled))
+                }
+                None => format!(
+                    "Company tags are {}. Use /companies on or /companies off to change it.",
+                    format_toggle(settings.companies)
+                ),
+            })
+            .await
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/bonus" => {
+            let reply = app.subscriptions.update(chat_id, |settings| {
+                if let Some(difficulty) = Difficulty::parse(argument) {
+                    info!(%chat_id, %difficulty, "Chat enabled bonus problems.");
+                    settings.bonus = true;
+                    settings.bonus_difficulty = Some(difficulty);
+                    return format!("A bonus {} problem will now be sent along with the daily.", difficulty);
+                }
+                match parse_toggle(argument) {
+                    Some(enabled) => {
+                        info!(%chat_id, enabled, "Chat set bonus problems.");
+                        settings.bonus = enabled;
+                        if !enabled {
+                            settings.bonus_difficulty = None;
+                        }
+                        format!("Bonus problems are now {}.", format_toggle(enabled))
+                    }
+                    None => format!(
+                        "Bonus problems are {}. Use /bonus on, /bonus off or /bonus easy|medium|hard to change it.",
+                        format_bonus(settings)
+                    ),
+                }
+            })
+            .await
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/preview" => {
+            let link_argument = argument
+                .split_once(char::is_whitespace)
+                .filter(|(target, _)| target.eq_ignore_ascii_case("link"))
+                .map(|(_, toggle)| toggle.trim())
+                .or_else(|| argument.eq_ignore_ascii_case("link").then_some(""));
+            let reply = app.subscriptions.update(chat_id, |settings| {
+                // "/preview link on|off" controls Telegram's link preview, separate from the statement preview
+                if let Some(link_argument) = link_argument {
+                    return match parse_toggle(link_argument) {
+                        Some(enabled) => {
+                            info!(%chat_id, enabled, "Chat set link preview.");
+                            settings.link_preview = enabled;
+                            format!("Link previews are now {}.", format_toggle(enabled))
+                        }
+                        None => format!(
+                            "Link previews are {}. Use /preview link on or /preview link off to change it.",
+                            format_toggle(settings.link_preview)
+                        ),
+                    };
+                }
+                match parse_toggle(argument) {
+                    Some(enabled) => {
+                        info!(%chat_id, enabled, "Chat set problem preview.");
+                        settings.preview = enabled;
+                        format!("Problem previews are now {}.", format_toggle(enabled))
+                    }
+                    None => format!(
+                        "Problem previews are {}. Use /preview on or /preview off to change it.",
+                        format_toggle(settings.preview)
+                    ),
+                }
+            })
+            .await
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/prefslang" => {
+            let reply = app.subscriptions.update(chat_id, |settings| {
+                if argument.eq_ignore_ascii_case("off") {
+                    info!(%chat_id, "Chat cleared its code language.");
+                    settings.code_lang = None;
+                    return "The daily challenge will no longer include starter code.".to_string();
+                }
+                match code_lang_slug(argument) {
+                    Some(slug) => {
+                        info!(%chat_id, code_lang = slug, "Chat set its code language.");
+                        settings.code_lang = Some(slug.to_string());
+                        format!("The daily challenge will include the {} starter code when available.", slug)
+                    }
+                    None => format!(
+                        "Current code language: {}. Use /prefslang <language> or /prefslang off. Supported: {}.",
+                        settings.code_lang.as_deref().unwrap_or("none"),
+                        code_lang_slugs().collect::<Vec<_>>().join(", ")
+                    ),
+                }
+            })
+            .await
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/lang" => {
+            let reply = app.subscriptions.update(chat_id, |settings| {
+                match Lang::parse(argument) {
+                    Some(new_lang) => {
+                        info!(%chat_id, lang = new_lang.code(), "Chat set language.");
+                        settings.lang = new_lang;
+                        i18n::text(new_lang, Key::LanguageSet).to_string()
+                    }
+                    None => format!(
+                        "{} {}",
+                        i18n::text(lang, Key::LanguageUsage),
+                        Lang::ALL.map(Lang::code).join(", ")
+                    ),
+                }
+            })
+            .await
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/pin" => {
+            let reply = app.subscriptions.update(chat_id, |settings| {
+                match argument.split_once(char::is_whitespace).unwrap_or((argument, "")) {
+                    ("notify", toggle) => match parse_toggle(toggle.trim()) {
+                        Some(enabled) => {
+                            info!(%chat_id, enabled, "Chat set pin notifications.");
+                            settings.pin_notify = enabled;
+                            format!("Pin notifications are now {}.", format_toggle(enabled))
+                        }
+                        None => format!(
+                            "Pin notifications are {}. Use /pin notify on or /pin notify off to change it.",
+                            format_toggle(settings.pin_notify)
+                        ),
+                    },
+                    _ => match parse_toggle(argument) {
+                        Some(enabled) => {
+                            info!(%chat_id, enabled, "Chat set pinning.");
+                            settings.pin_disabled = !enabled;
+                            format!("Pinning daily challenges is now {}.", format_toggle(enabled))
+                        }
+                        None => format!(
+                            "Pinning daily challenges is {}. Use /pin on or /pin off to change it.",
+                            format_toggle(!settings.pin_disabled)
+                        ),
+                    },
+                }
+            })
+            .await
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/template" => {
+            let reply = app.subscriptions.update(chat_id, |settings| match argument {
+                "" => match &settings.template {
+                    Some(custom) => format!("This chat's template:\n{}\n\nUse /template off to go back to the default message.", custom),
+                    None => format!("This chat uses the default message. Set your own with /template <text>, using {}.", format_placeholders()),
+                },
+                "off" => {
+                    info!(%chat_id, "Chat reset its message template.");
+                    settings.template = None;
+                    "This chat now uses the default message.".to_string()
+                }
+                custom => match template::validate(custom) {
+                    Ok(()) => {
+                        info!(%chat_id, "Chat set a message template.");
+                        settings.template = Some(custom.to_string());
+                        "Template saved, it will be used for the next challenge.".to_string()
+                    }
+                    Err(problem) => format!("{} Templates can use {}.", problem, format_placeholders()),
+                },
+            })
+            .await
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/autounpin" => {
+            let reply = app.subscriptions.update(chat_id, |settings| {
+                match argument.split_once(char::is_whitespace).unwrap_or((argument, "")) {
+                    ("delete", toggle) => match parse_toggle(toggle.trim()) {
+                        Some(enabled) => {
+                            info!(%chat_id, enabled, "Chat set deleting previous messages.");
+                            settings.delete_previous = enabled;
+                            format!("Deleting the previous challenge message is now {}.", format_toggle(enabled))
+                        }
+                        None => format!(
+                            "Deleting the previous challenge message is {}. Use /autounpin delete on or /autounpin delete off to change it.",
+                            format_toggle(settings.delete_previous)
+                        ),
+                    },
+                    _ => match parse_toggle(argument) {
+                        Some(enabled) => {
+                            info!(%chat_id, enabled, "Chat set auto-unpin.");
+                            settings.auto_unpin = enabled;
+                            format!("Unpinning the previous challenge is now {}.", format_toggle(enabled))
+                        }
+                        None => format!(
+                            "Unpinning the previous challenge is {}. Use /autounpin on or /autounpin off to change it.",
+                            format_toggle(settings.auto_unpin)
+                        ),
+                    },
+                }
+            })
+            .await
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        "/thread" => {
+            let reply = app.subscriptions.update(chat_id, |settings| {
+                match parse_toggle(argument) {
+                    Some(enabled) => {
+                        info!(%chat_id, enabled, "Chat set reply thread.");
+                        settings.reply_thread = enabled;
+                        // Turning it back on later starts a fresh thread
+                        settings.anchor_message_id = None;
+                        if enabled {
+                            "The next daily posts a pinned anchor message, and every daily after it replies to that message.".to_string()
+                        } else {
+                            "Dailies are no longer posted as replies.".to_string()
+                        }
+                    }
+                    None => format!(
+                        "Posting dailies as replies to one pinned message is {}. Use /thread on or /thread off to change it.",
+                        format_toggle(settings.reply_thread)
+                    ),
+                }
+            })
+            .await
+            .unwrap_or_else(|| i18n::text(lang, Key::NotSubscribed).to_string());
+            bot.send_message(chat_id, reply).send().await?;
+        }
+        // Groups see plenty of commands meant for other bots, so only private chats hear about typos
+        _ if command.starts_with('/') && message.chat.is_private() => {
+            debug!(%chat_id, command, "Unknown command.");
+            bot.send_message(chat_id, i18n::text(lang, Key::UnknownCommand)).send().await?;
+        }
+        _ => {
+            // do nothing
+        }
+    }
+    respond(())
+}
+
+#[tokio::main]
+async fn main() {
+    // Load environment variables
+    dotenv().ok();
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .init();
+    info!("Loading environment variables...");
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            error!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    if config.dry_run {
+        warn!("DRY_RUN is set, scheduled messages and broadcasts will only be logged.");
+    }
+
+    // Serve the health endpoints right away so probes can tell the bot is still starting
+    let shutdown = CancellationToken::new();
+    let health = Arc::new(Health::default());
+    let health_server = config.health_port.map(|port| {
+        let health = Arc::clone(&health);
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            if let Err(err) = health::serve(([0, 0, 0, 0], port).into(), health, shutdown).await {
+                error!(error = ?err, "Health server failed.");
+            }
+        })
+    });
+
+    // Initialize the bot and HTTP client
+    info!("Initializing bot and client...");
+    let bot = Bot::new(config.bot_token);
+
+    // Check the token before anything else starts, a typo would otherwise only show up deep in the dispatcher
+    match bot.get_me().send().await {
+        Ok(me) => info!(username = %me.username(), "Logged in to Telegram."),
+        Err(RequestError::Api(err)) => {
+            error!(error = %err, "Invalid bot token, check TELOXIDE_TOKEN.");
+            std::process::exit(1);
+        }
+        Err(err) => {
+            error!(error = %err, "Couldn't reach Telegram to validate the bot token.");
+            std::process::exit(1);
+        }
+    }
+    let client = build_leetcode_client(
+        &config.leetcode_base_url,
+        config.leetcode_user_agent.as_deref(),
+        config.leetcode_credentials.as_ref(),
+        config.leetcode_timeout,
+    )
+    .expect("Failed to build LeetCode client");
+
+    // Open the database if configured, otherwise keep using the chat IDs file
+    let storage: Arc<dyn Storage> = match (config.database_url, config.chat_ids_file_path) {
+        (Some(database_url), chat_ids_file_path) => Arc::new(
+            SqliteStorage::connect(&database_url, chat_ids_file_path.as_deref())
+                .await
+                .expect("Failed to open DATABASE_URL"),
+        ),
+        (None, chat_ids_file_path) => {
+            let path = chat_ids_file_path.expect("checked by Config::load");
+            let storage = FileStorage::new(path).with_load_retries(config.chat_ids_load_retries);
+            let bot = bot.clone();
+            let admin_chat_id = config.admin_chat_id;
+            Arc::new(storage.on_write_failure(move |alert| {
+                error!(alert, "Subscriptions can't be saved.");
+                let (bot, Some(admin_chat_id)) = (bot.clone(), admin_chat_id) else {
+                    return;
+                };
+                tokio::spawn(async move {
+                    if let Err(err) = bot.send_message(admin_chat_id, alert).send().await {
+                        warn!(error = ?err, "Failed to tell the admin that subscriptions can't be saved.");
+                    }
+                });
+            }))
+        }
+    };
+
+    // Load chat IDs from storage
+    info!("Loading chat IDs...");
+    let subscriptions = Subscriptions::load(Arc::clone(&storage), config.max_subscribers).await.expect("Failed to load chat IDs");
+    info!(count = subscriptions.len().await, "Chat IDs loaded.");
+    let paused = storage.load_paused().await.expect("Failed to load the paused state");
+    if paused {
+        warn!("Scheduled sends are paused, send /resume from the admin chat to start them again.");
+    }
+    let routes = storage.load_routes().await.expect("Failed to load the difficulty routes");
+    let featured = storage.load_featured().await.expect("Failed to load the featured problem");
+    health.mark_chats_loaded();
+    let app = Arc::new(App {
+        bot: bot.clone(),
+        client,
+        subscriptions,
+        trigger_time: config.trigger_time,
+        send_jitter: config.send_jitter,
+        send_permits: Semaphore::new(config.send_concurrency),
+        storage,
+        weekly_day: config.weekly_day,
+        weekly_time: config.weekly_time,
+        reminder_delay: config.reminder_delay,
+        daily_retry_window: config.daily_retry_window,
+        started_at: Utc::now(),
+        last_run: Mutex::new(None),
+        preview_chars: config.preview_chars,
+        message_footer: config.message_footer,
+        daily_cache: Mutex::new(None),
+        schema_drift: Mutex::new(None),
+        profile_cache: Mutex::new(HashMap::new()),
+        last_feedback: Mutex::new(HashMap::new()),
+        rate_limiter: RateLimiter::per_second(MESSAGES_PER_SECOND),
+        admin_chat_id: config.admin_chat_id,
+        paused: AtomicBool::new(paused),
+        routes: Mutex::new(routes),
+        featured: Mutex::new(featured),
+        problem_index: ProblemIndex::default(),
+        dead_letters: Mutex::new(VecDeque::new()),
+        log_channel_id: config.log_channel_id,
+        dry_run: config.dry_run,
+        send_repeated_daily: config.send_repeated_daily,
+        quiet_start: config.quiet_start,
+        health,
+    });
+
+    if config.prune_on_startup {
+        prune_unreachable_chats(&app).await;
+    }
+
+    // Keep the problem index fresh; the first tick fills it right away, until then random picks ask LeetCode directly
+    if !config.problem_index_refresh.is_zero() {
+        let app = Arc::clone(&app);
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            let mut interval = interval(config.problem_index_refresh);
+            loop {
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = interval.tick() => {}
+                }
+                let _ = refresh_problem_index(&app).await;
+            }
+        });
+    }
+
+    // Every channel the daily is sent through
+    #[cfg_attr(not(feature = "discord"), allow(unused_mut))]
+    let mut notifiers: Vec<Arc<dyn Notifier>> = vec![Arc::new(TelegramNotifier { app: Arc::clone(&app) })];
+    #[cfg(feature = "discord")]
+    if let Some(url) = config.discord_webhook_url.clone() {
+        notifiers.push(Arc::new(notifier::DiscordWebhook::new(url, config.trigger_time, config.dry_run)));
+    }
+
+    // Clone necessary references for the spawned task
+    let scheduler_app = Arc::clone(&app);
+    let scheduler_shutdown = shutdown.clone();
+
+    // Spawn a task that checks every minute which chats reached their trigger time
+    info!("Spawning task to send daily challenges...");
+    let scheduler = tokio::spawn(async move {
+        let mut interval = interval(Duration::from_secs(60));
+        let mut last_check = Utc::now();
+        scheduler_app.health.set_scheduler_running(true);
+
+        // Send today's challenge to chats that missed it while the bot was down
+        let trigger = Trigger::Startup { at: last_check };
+        let missed = due_notifiers(&notifiers, trigger).await;
+        if !missed.is_empty() && scheduler_app.paused.load(Ordering::Relaxed) {
+            info!(notifiers = missed.len(), "Paused, not catching up on missed daily challenges.");
+        } else if !missed.is_empty() {
+            info!(notifiers = missed.len(), "Catching up on missed daily challenges.");
+            tokio::spawn(run_notifiers(Arc::clone(&scheduler_app), missed, trigger));
+        }
+
+        loop {
+            tokio::select! {
+                _ = scheduler_shutdown.cancelled() => break,
+                _ = interval.tick() => {}
+            }
+            let now = Utc::now();
+            let chats = scheduler_app.subscriptions.snapshot().await;
+            let weekly_chats: Vec<(ChatId, ChatSettings)> = chats
+                .iter()
+                .filter(|(_, settings)| {
+                    settings.weekly_digest
+                        && weekly_trigger_passed(
+                            last_check,
+                            now,
+                            scheduler_app.weekly_day,
+                            scheduler_app.weekly_time,
+                            settings.timezone,
+                        )
+                })
+                .cloned()
+                .collect();
+            let reminder_delay = chrono::Duration::from_std(scheduler_app.reminder_delay).unwrap_or_default();
+            let reminder_chats: Vec<(ChatId, ChatSettings)> = chats
+                .into_iter()
+                .filter(|(_, settings)| {
+                    settings.reminder_due(local_now(now, settings.timezone), scheduler_app.trigger_time, reminder_delay)
+                })
+                .collect();
+            let trigger = Trigger::Passed { since: last_check, now };
+            let due = due_notifiers(&notifiers, trigger).await;
+            last_check = now;
+            if scheduler_app.paused.load(Ordering::Relaxed) {
+                if !(due.is_empty() && weekly_chats.is_empty() && reminder_chats.is_empty()) {
+                    info!(
+                        notifiers = due.len(),
+                        weekly_chats = weekly_chats.len(),
+                        reminder_chats = reminder_chats.len(),
+                        "Paused, skipping the run."
+                    );
+                }
+                continue;
+            }
+            if !weekly_chats.is_empty() {
+                info!(chats = weekly_chats.len(), "Weekly recap triggered.");
+                let app = Arc::clone(&scheduler_app);
+                tokio::spawn(async move {
+                    if let Err(err) = send_weekly_digest(app, weekly_chats).await {
+                        error!(error = ?err, "Error sending weekly recap.");
+                    }
+                });
+            }
+            if !reminder_chats.is_empty() {
+                info!(chats = reminder_chats.len(), "Solve reminders triggered.");
+                tokio::spawn(send_reminders(Arc::clone(&scheduler_app), reminder_chats));
+            }
+            if due.is_empty() {
+                continue;
+            }
+            info!(notifiers = due.len(), "Triggered.");
+            tokio::spawn(run_notifiers(Arc::clone(&scheduler_app), due, trigger));
+        }
+        scheduler_app.health.set_scheduler_running(false);
+        info!("Scheduler stopped.");
+    });
+
+    // Show the supported commands in Telegram's command menu
+    let bot_commands: Vec<BotCommand> = COMMANDS
+        .iter()
+        .map(|(command, _, description)| BotCommand::new(*command, *description))
+        .collect();
+    if let Err(err) = bot.set_my_commands(bot_commands).send().await {
+        warn!(error = ?err, "Failed to register bot commands.");
+    }
+
+    // Handle incoming messages
+    info!("Starting message handler...");
+    let handler = dptree::entry()
+        .branch(Update::filter_message().endpoint(handle_message))
+        .branch(Update::filter_callback_query().endpoint(handle_callback));
+
+    let mut dispatcher = Dispatcher::builder(bot.clone(), handler)
+        .dependencies(dptree::deps![Arc::clone(&app)])
+        .enable_ctrlc_handler()
+        .build();
+
+    // Docker and systemd stop the bot with SIGTERM, handle it like Ctrl+C
+    let shutdown_token = dispatcher.shutdown_token();
+    let mut sigterm = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+    tokio::spawn(async move {
+        sigterm.recv().await;
+        info!("Received SIGTERM, shutting down...");
+        if let Err(err) = shutdown_token.shutdown() {
+            warn!(error = ?err, "Dispatcher was not running.");
+        }
+    });
+
+    match config.bot_mode {
+        BotMode::Polling => dispatcher.dispatch().await,
+        BotMode::Webhook { url, port, secret_token } => {
+            info!(%url, port, "Receiving updates through the webhook...");
+            let mut options = webhooks::Options::new(([0, 0, 0, 0], port).into(), url);
+            if let Some(secret_token) = secret_token {
+                options = options.secret_token(secret_token);
+            }
+            let listener = match webhooks::axum(bot, options).await {
+                Ok(listener) => listener,
+                Err(err) => {
+                    error!(error = %err, "Failed to set up the webhook.");
+                    std::process::exit(1);
+                }
+            };
+            dispatcher
+                .dispatch_with_listener(listener, LoggingErrorHandler::with_custom_text("Error from the webhook listener"))
+                .await;
+        }
+    }
+
+    // Stop the scheduler and make sure the latest state is on disk before exiting
+    shutdown.cancel();
+    if let Err(err) = scheduler.await {
+        error!(error = ?err, "Scheduler task failed.");
+    }
+    if let Some(health_server) = health_server {
+        let _ = health_server.await;
+    }
+    app.subscriptions.flush().await;
+    info!("Shutdown complete.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(datetime: &str) -> DateTime<Utc> {
+        NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M:%S").unwrap().and_utc()
+    }
+
+    fn time(time: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(time, "%H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn parse_weekdays_sorts_and_validates_days() {
+        assert_eq!(parse_weekdays("fri, mon,Wed,mon"), Some(vec![Weekday::Mon, Weekday::Wed, Weekday::Fri]));
+        assert_eq!(parse_weekdays("weekends"), Some(vec![Weekday::Sat, Weekday::Sun]));
+        assert_eq!(parse_weekdays("all"), Some(Vec::new()));
+        assert_eq!(parse_weekdays("mon,tue,wed,thu,fri,sat,sun"), Some(Vec::new()));
+        assert_eq!(parse_weekdays("mon,funday"), None);
+        assert_eq!(parse_weekdays(","), None);
+    }
+
+    #[test]
+    fn send_delays_fill_one_slot_each() {
+        let window = Duration::from_secs(100);
+        let mut delays = send_delays(4, window);
+        delays.sort();
+        for (i, delay) in delays.iter().enumerate() {
+            let slot_start = Duration::from_secs(25 * i as u64);
+            assert!(*delay >= slot_start && *delay < slot_start + Duration::from_secs(25), "{:?}", delays);
+        }
+        assert_eq!(send_delays(3, Duration::ZERO), [Duration::ZERO; 3]);
+    }
+
+    fn question_titled(title: &str) -> DailyQuestion {
+        DailyQuestion {
+            title: title.to_string(),
+            slug: "compare".to_string(),
+            link: "https://leetcode.com/problems/compare/".to_string(),
+            difficulty: Some(Difficulty::Medium),
+            ac_rate: None,
+            total_submissions: None,
+            tags: vec![TopicTag { name: "Bits & Bytes".to_string(), slug: "bits".to_string() }],
+            paid_only: false,
+            code_snippets: Vec::new(),
+            content: None,
+            companies: Vec::new(),
+            hints: 0,
+        }
+    }
+
+    #[test]
+    fn format_question_bolds_and_escapes_the_title() {
+        let question = question_titled("Compare <a> & <b>");
+        assert_eq!(
+            format_question(&question),
+            "🟡 <b>Compare &lt;a&gt; &amp; &lt;b&gt;</b> (Medium)\nhttps://leetcode.com/problems/compare/\nTopics: Bits &amp; Bytes"
+        );
+    }
+
+    #[test]
+    fn daily_message_escapes_every_dynamic_part() {
+        let mut question = question_titled("Rock & Roll <Hard>");
+        question.content = Some("Return a < b && c > d".to_string());
+        let mut text = format_daily_message(Lang::En, Key::DailyLabel, Some(&question));
+        text.push_str(&format_preview(&question, 300).unwrap());
+
+        assert!(text.contains("<b>Rock &amp; Roll &lt;Hard&gt;</b>"), "{}", text);
+        assert!(text.contains("<i>Return a &lt; b &amp;&amp; c &gt; d</i>"), "{}", text);
+        // Only the tags the bot adds itself remain once the escaped parts are removed
+        let stripped = text.replace("<b>", "").replace("</b>", "").replace("<i>", "").replace("</i>", "");
+        assert!(!stripped.contains('<') && !stripped.contains('>'), "{}", text);
+        assert!(!stripped.replace("&amp;", "").replace("&lt;", "").replace("&gt;", "").contains('&'), "{}", text);
+    }
+
+    #[test]
+    fn format_contest_escapes_the_link() {
+        let now = Utc::now();
+        let contest = Contest {
+            title: "Weekly <Contest>".to_string(),
+            link: "https://leetcode.com/contest/weekly-contest-1?a=1&b=2".to_string(),
+            start_time: now + chrono::Duration::hours(2),
+            duration: std::time::Duration::from_secs(90 * 60),
+        };
+        let text = format_contest(&contest, now, None);
+        assert!(text.contains("<a href=\"https://leetcode.com/contest/weekly-contest-1?a=1&amp;b=2\">"), "{}", text);
+        assert!(text.contains("Weekly &lt;Contest&gt;</a>"), "{}", text);
+    }
+
+    #[test]
+    fn daily_keyboard_skips_invalid_links() {
+        let keyboard = daily_keyboard(Lang::En, Some("https://leetcode.com/problems/two-sum/"));
+        assert_eq!(keyboard.inline_keyboard[0].len(), 3);
+        let keyboard = daily_keyboard(Lang::En, Some("not a link"));
+        assert_eq!(keyboard.inline_keyboard[0].len(), 2);
+    }
+
+    #[test]
+    fn problem_slug_accepts_titles_and_links() {
+        assert_eq!(problem_slug("two-sum"), "two-sum");
+        assert_eq!(problem_slug(" Two Sum "), "two-sum");
+        assert_eq!(problem_slug("https://leetcode.com/problems/two-sum/"), "two-sum");
+    }
+
+    #[test]
+    fn truncate_chars_cuts_on_char_boundaries() {
+        assert_eq!(truncate_chars("short", 10), "short");
+        assert_eq!(truncate_chars("exactly10!", 10), "exactly10!");
+        assert_eq!(truncate_chars("naïve café au lait", 10), "naïve caf…");
+        assert_eq!(truncate_chars("日本語のテキスト", 4), "日本語…");
+    }
+
+    #[test]
+    fn next_trigger_just_before_is_later_today() {
+        let duration = duration_until_next_trigger(utc("2024-03-10 09:59:59"), time("10:00:00"), Some(Tz::UTC));
+        assert_eq!(duration, chrono::Duration::seconds(1));
+    }
+
+    #[test]
+    fn next_trigger_exactly_now_is_tomorrow() {
+        let duration = duration_until_next_trigger(utc("2024-03-10 10:00:00"), time("10:00:00"), Some(Tz::UTC));
+        assert_eq!(duration, chrono::Duration::seconds(24 * 60 * 60));
+    }
+
+    #[test]
+    fn next_trigger_just_after_is_tomorrow() {
+        let duration = duration_until_next_trigger(utc("2024-03-10 10:00:01"), time("10:00:00"), Some(Tz::UTC));
+        assert_eq!(duration, chrono::Duration::seconds(24 * 60 * 60 - 1));
+    }
+
+    #[test]
+    fn next_trigger_rolls_over_midnight() {
+        let duration = duration_until_next_trigger(utc("2024-03-10 23:59:00"), time("00:01:00"), Some(Tz::UTC));
+        assert_eq!(duration, chrono::Duration::seconds(2 * 60));
+    }
+
+    #[test]
+    fn next_trigger_uses_the_chat_timezone() {
+        // 08:30 UTC is 09:30 in Berlin during winter time
+        let duration = duration_until_next_trigger(utc("2024-01-15 08:30:00"), time("10:00:00"), Some(chrono_tz::Europe::Berlin));
+        assert_eq!(duration, chrono::Duration::seconds(30 * 60));
+    }
+
+    #[test]
+    fn send_log_counts_outcomes_and_lists_failed_chats() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+        let record = |chat_id, outcome| SendRecord {
+            at: utc("2024-03-10 10:00:05"),
+            date,
+            chat_id: ChatId(chat_id),
+            slug: Some("two-sum".to_string()),
+            outcome,
+        };
+        let records = vec![record(1, SendOutcome::Sent), record(2, SendOutcome::Failed), record(3, SendOutcome::Removed)];
+        let text = format_send_log(date, &records);
+        assert!(text.contains("1 sent, 2 failed"));
+        assert!(text.contains("Problems: two-sum"));
+        assert!(text.contains("<code>2</code> at 10:00:05"));
+        assert!(text.contains("<code>3</code> at 10:00:05, unsubscribed"));
+        assert!(!text.contains("<code>1</code>"));
+        assert!(format_send_log(date, &[]).contains("Nothing was sent"));
+    }
+
+    #[test]
+    fn run_report_names_the_problem_and_counts() {
+        let mut summary = SendSummary { sent: 40, failed: 2, removed: 1, unpinned: 0 };
+        let text = format_run_report(Some(&question_titled("Two Sum")), 42, summary);
+        assert_eq!(text, "Daily run: Two Sum\n42 subscribers, 40 sent, 2 failed, 1 removed");
+        summary.record_attempt(&Attempt::Sent { pinned: false });
+        assert_eq!(summary.to_string(), "41 sent, 2 failed, 1 removed, pin failed in 1");
+        assert!(format_run_report(None, 0, SendSummary::default()).starts_with("Daily run: no daily question"));
+    }
+
+    #[test]
+    fn usernames_are_checked_before_reaching_leetcode() {
+        assert!(valid_username("alice_01"));
+        assert!(valid_username("a.b-c"));
+        assert!(!valid_username(""));
+        assert!(!valid_username("alice bob"));
+        assert!(!valid_username("<b>"));
+    }
+
+    #[test]
+    fn leaderboard_ranks_by_solved_and_lists_failures() {
+        let profile = |username: &str, easy, medium, hard| Profile { username: username.to_string(), easy, medium, hard };
+        let text = format_leaderboard(
+            vec![profile("alice", 10, 5, 0), profile("bob", 20, 10, 2), profile("carol", 5, 5, 5)],
+            &["dave".to_string()],
+        );
+        assert_eq!(
+            text,
+            "<b>Leaderboard</b>\n1. bob - 32 solved (20/10/2)\n2. carol - 15 solved (5/5/5)\n3. alice - 15 solved (10/5/0)\nCouldn't load: dave"
+        );
+    }
+
+    // How many times the trigger passes between the two instants, checked every minute like the scheduler does
+    fn triggers_between(from: &str, to: &str, trigger_time: NaiveTime, timezone: Option<Tz>) -> usize {
+        let (mut last_check, end) = (utc(from), utc(to));
+        let mut count = 0;
+        while last_check < end {
+            let now = last_check + chrono::Duration::minutes(1);
+            count += usize::from(trigger_passed(last_check, now, trigger_time, timezone));
+            last_check = now;
+        }
+        count
+    }
+
+    #[test]
+    fn trigger_in_the_skipped_hour_fires_once_when_clocks_go_forward() {
+        // Berlin skips from 02:00 to 03:00 on 2024-03-31
+        let berlin = Some(chrono_tz::Europe::Berlin);
+        assert_eq!(triggers_between("2024-03-30 22:00:00", "2024-03-31 22:00:00", time("02:30:00"), berlin), 1);
+        assert_eq!(triggers_between("2024-03-30 22:00:00", "2024-03-31 22:00:00", time("10:00:00"), berlin), 1);
+        // 10:00 is now two hours ahead of UTC instead of one
+        let duration = duration_until_next_trigger(utc("2024-03-31 00:00:00"), time("10:00:00"), berlin);
+        assert_eq!(duration, chrono::Duration::seconds(8 * 60 * 60));
+    }
+
+    #[test]
+    fn trigger_in_the_repeated_hour_fires_once_when_clocks_go_back() {
+        // Berlin goes from 03:00 back to 02:00 on 2024-10-27
+        let berlin = Some(chrono_tz::Europe::Berlin);
+        assert_eq!(triggers_between("2024-10-26 21:00:00", "2024-10-27 23:00:00", time("02:30:00"), berlin), 1);
+        assert_eq!(triggers_between("2024-10-26 21:00:00", "2024-10-27 23:00:00", time("10:00:00"), berlin), 1);
+        let duration = duration_until_next_trigger(utc("2024-10-27 00:00:00"), time("10:00:00"), berlin);
+        assert_eq!(duration, chrono::Duration::seconds(9 * 60 * 60));
+    }
+
+    #[test]
+    fn next_send_skips_weekdays_off_and_reports_snoozes() {
+        // 2024-03-08 is a Friday
+        let now = utc("2024-03-08 12:00:00");
+        let settings = ChatSettings { timezone: Some(Tz::UTC), ..ChatSettings::default() };
+        assert_eq!(format_next_send(now, &settings, time("10:00:00")), "Next daily challenge in 22h 0m (Sat 2024-03-09 10:00).");
+
+        let weekdays = ChatSettings { days: vec![Weekday::Mon], ..settings.clone() };
+        assert_eq!(format_next_send(now, &weekdays, time("10:00:00")), "Next daily challenge in 70h 0m (Mon 2024-03-11 10:00).");
+
+        let snoozed = ChatSettings { snoozed_until: NaiveDate::from_ymd_opt(2024, 3, 10), ..settings };
+        assert!(format_next_send(now, &snoozed, time("10:00:00")).starts_with("Snoozed, daily challenges resume on Sun 2024-03-10 10:00, in 46h 0m."));
+    }
+
+    #[test]
+    fn dead_letters_are_listed_newest_first() {
+        let letter = |chat_id, error: &str, attempts| DeadLetter {
+            at: utc("2024-03-10 10:00:00"),
+            chat_id: ChatId(chat_id),
+            error: error.to_string(),
+            attempts,
+        };
+        let dead_letters = VecDeque::from([letter(1, "network <error>", 4), letter(2, "message is too long", 1)]);
+        assert_eq!(
+            format_dead_letters(&dead_letters),
+            "<b>Failed sends</b> (2 kept, newest first)\n<code>2</code> at 2024-03-10 10:00 after 1 attempt: message is too long\n<code>1</code> at 2024-03-10 10:00 after 4 attempts: network &lt;error&gt;"
+        );
+        assert!(format_dead_letters(&VecDeque::new()).starts_with("No failed sends"));
+    }
+
+    #[test]
+    fn long_messages_are_split_under_the_limit() {
+        let mut question = question_titled("Long");
+        question.content = Some("word ".repeat(2000));
+        let mut text = format_daily_message(Lang::En, Key::DailyLabel, Some(&question));
+        text.push_str(&format_preview(&question, 3000).unwrap());
+        text.push_str(&format!("\n\n{}", "x".repeat(6000)));
+        assert!(text.chars().count() > 2 * MAX_MESSAGE_CHARS);
+
+        let parts = split_message(&text, MAX_MESSAGE_CHARS);
+        assert_eq!(parts.len(), 3);
+        assert!(parts.iter().all(|part| visible_len(part) <= MAX_MESSAGE_CHARS));
+        // The paragraph break keeps the header and the preview together and intact
+        assert!(parts[0].starts_with("Today's LeetCode Challenge:") && parts[0].ends_with("</i>"));
+        assert_eq!(parts[1..].concat(), "x".repeat(6000));
+        assert_eq!(split_message("short", MAX_MESSAGE_CHARS), ["short"]);
+    }
+
+    #[test]
+    fn split_messages_keep_their_html_balanced() {
+        let preview = format!("\n\n<i>{}</i>", "Given an array &amp; a target.\n\n".repeat(20));
+        let snippet = format!("\n\n<pre><code class=\"language-rust\">{}</code></pre>", "let x = a &lt; b;\n".repeat(20));
+        let text = format!("<b>Title</b>{}{}", preview, snippet);
+
+        let parts = split_message(&text, 100);
+        assert!(parts.len() > 4);
+        for part in &parts {
+            assert!(visible_len(part) <= 100, "{}", part);
+            let mut open = Vec::new();
+            track_open_tags(part, &mut open);
+            assert!(open.is_empty(), "{}", part);
+        }
+        assert!(parts.iter().any(|part| part.starts_with("<i>") && part.ends_with("</i>")));
+        assert!(parts.iter().any(|part| part.starts_with("<pre><code class=\"language-rust\">") && part.ends_with("</code></pre>")));
+
+        // Without line breaks a cut still never lands inside a tag or an entity
+        let parts = split_message(&format!("<i>{}</i>", "a&amp;b".repeat(50)), 10);
+        for part in &parts {
+            assert!(part.starts_with("<i>") && part.ends_with("</i>"), "{}", part);
+            assert_eq!(part.matches('&').count(), part.matches("&amp;").count(), "{}", part);
+        }
+    }
+
+    #[test]
+    fn routed_chats_only_take_their_difficulties() {
+        assert_eq!(parse_route("hard -100, -200"), Some((Difficulty::Hard, BTreeSet::from([ChatId(-200), ChatId(-100)]))));
+        assert_eq!(parse_route("Easy off"), Some((Difficulty::Easy, BTreeSet::new())));
+        assert_eq!(parse_route("hard"), None);
+        assert_eq!(parse_route("extreme -100"), None);
+        assert_eq!(parse_route("hard channel"), None);
+
+        let routes = Routes::from([
+            (Difficulty::Easy, BTreeSet::from([ChatId(-100)])),
+            (Difficulty::Medium, BTreeSet::from([ChatId(-100)])),
+            (Difficulty::Hard, BTreeSet::from([ChatId(-200)])),
+        ]);
+        let filtered = ChatSettings { difficulties: BTreeSet::from([Difficulty::Easy]), ..ChatSettings::default() };
+        let mut chat_ids =
+            vec![(ChatId(-100), ChatSettings::default()), (ChatId(-200), filtered.clone()), (ChatId(1), filtered)];
+        apply_routes(&mut chat_ids, &routes);
+        assert_eq!(chat_ids[0].1.difficulties, BTreeSet::from([Difficulty::Easy, Difficulty::Medium]));
+        assert_eq!(chat_ids[1].1.difficulties, BTreeSet::from([Difficulty::Hard]));
+        // Chats without a route keep their own filter
+        assert_eq!(chat_ids[2].1.difficulties, BTreeSet::from([Difficulty::Easy]));
+    }
+
+    #[test]
+    fn count_reply_reads_naturally() {
+        assert_eq!(format_count(true, 42, 5), "42 chats get the daily challenge across 5 timezones. This chat is one of 42!");
+        assert_eq!(format_count(false, 1, 1), "1 chat gets the daily challenge across 1 timezone.");
+    }
+
+    #[test]
+    fn breakdown_counts_this_months_dailies_of_the_same_difficulty() {
+        let daily = |difficulty| DailyQuestion { difficulty: Some(difficulty), ..question_titled("Daily") };
+        let date = |day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+        let history = vec![
+            (NaiveDate::from_ymd_opt(2024, 2, 29).unwrap(), daily(Difficulty::Hard)),
+            (date(1), daily(Difficulty::Hard)),
+            (date(2), daily(Difficulty::Easy)),
+            (date(3), daily(Difficulty::Hard)),
+            (date(4), daily(Difficulty::Hard)),
+        ];
+        let text = format_breakdown(date(4), &daily(Difficulty::Hard), &history);
+        assert_eq!(text.as_deref(), Some("\n🔴 3rd Hard this month"));
+        let text = format_breakdown(date(4), &daily(Difficulty::Easy), &history);
+        assert_eq!(text.as_deref(), Some("\n🟢 2nd Easy this month"));
+        // The first of the month needs no history, later days need the first
+        assert_eq!(format_breakdown(date(1), &daily(Difficulty::Medium), &[]).as_deref(), Some("\n🟡 1st Medium this month"));
+        assert_eq!(format_breakdown(date(4), &daily(Difficulty::Hard), &history[2..]), None);
+        assert_eq!(ordinal(12), "12th");
+        assert_eq!(ordinal(22), "22nd");
+    }
+
+    #[test]
+    fn featured_problem_is_labeled_and_described() {
+        let question = question_titled("Two Sum");
+        let text = format_daily_message(Lang::En, Key::FeaturedLabel, Some(&question));
+        assert!(text.contains("Featured: 🟡 <b>Two Sum</b>"));
+        assert!(format_daily_message(Lang::Es, Key::FeaturedLabel, Some(&question)).contains("Destacado:"));
+
+        assert!(format_featured(None).starts_with("No override set."));
+        let pending = FeaturedProblem { question: question.clone(), date: None };
+        assert!(format_featured(Some(&pending)).starts_with("The next scheduled send features 🟡 <b>Two Sum</b>"));
+        let claimed = FeaturedProblem { question, date: NaiveDate::from_ymd_opt(2024, 3, 10) };
+        assert!(format_featured(Some(&claimed)).contains("featured instead of the daily on 2024-03-10 (UTC)"));
+    }
+
+    #[test]
+    fn version_names_the_release_and_commit() {
+        let version = format_version();
+        assert!(version.starts_with(&format!("leetcode_bot {} (commit ", env!("CARGO_PKG_VERSION"))));
+        assert!(!version.contains("(commit )"));
+    }
+
+    #[test]
+    fn hint_count_is_shown_only_when_there_are_hints() {
+        let mut question = question_titled("Two Sum");
+        assert_eq!(format_hints(&question), None);
+        question.hints = 1;
+        assert_eq!(format_hints(&question).as_deref(), Some("\n💡 1 hint available"));
+        question.hints = 3;
+        assert_eq!(format_hints(&question).as_deref(), Some("\n💡 3 hints available"));
+    }
+
+    // An app talking to the mock LeetCode server only, without admin chat or log channel
+    async fn test_app(leetcode_url: &str, daily_retry_window: Duration) -> App {
+        let storage: Arc<dyn Storage> = Arc::new(storage::MemoryStorage::default());
+        App {
+            bot: Bot::new("0:test"),
+            client: build_leetcode_client(leetcode_url, None, None, Duration::from_secs(5)).unwrap(),
+            subscriptions: Subscriptions::load(Arc::clone(&storage), None).await.unwrap(),
+            trigger_time: NaiveTime::MIN,
+            send_jitter: Duration::ZERO,
+            send_permits: Semaphore::new(1),
+            storage,
+            weekly_day: Weekday::Sun,
+            weekly_time: NaiveTime::MIN,
+            reminder_delay: Duration::ZERO,
+            daily_retry_window,
+            started_at: Utc::now(),
+            last_run: Mutex::new(None),
+            preview_chars: 0,
+            message_footer: None,
+            daily_cache: Mutex::new(None),
+            schema_drift: Mutex::new(None),
+            profile_cache: Mutex::new(HashMap::new()),
+            last_feedback: Mutex::new(HashMap::new()),
+            rate_limiter: RateLimiter::per_second(MESSAGES_PER_SECOND),
+            admin_chat_id: None,
+            paused: AtomicBool::new(false),
+            routes: Mutex::new(Routes::new()),
+            featured: Mutex::new(None),
+            problem_index: ProblemIndex::default(),
+            dead_letters: Mutex::new(VecDeque::new()),
+            log_channel_id: None,
+            dry_run: true,
+            send_repeated_daily: false,
+            quiet_start: false,
+            health: Arc::new(Health::default()),
+        }
+    }
+
+    #[tokio::test]
+    async fn waiting_for_the_daily_rides_out_rate_limits() {
+        use wiremock::matchers::method;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(429)).up_to_n_times(1).mount(&server).await;
+        let body = serde_json::json!({"data": {"activeDailyCodingChallengeQuestion": {
+            "date": "2024-03-10",
+            "link": "/problems/two-sum/",
+            "question": {"title": "Two Sum", "titleSlug": "two-sum", "difficulty": "Easy", "topicTags": []}
+        }}});
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(200).set_body_json(body)).mount(&server).await;
+        let app = test_app(&server.uri(), Duration::from_secs(5)).await;
+        assert!(wait_for_daily_question(&app, Duration::from_millis(10)).await);
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+
+        // A response of another schema won't change by asking again
+        let server = MockServer::start().await;
+        let body = serde_json::json!({"errors": [{"message": "Cannot query field \"activeDailyCodingChallengeQuestion\""}]});
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(200).set_body_json(body)).mount(&server).await;
+        let app = test_app(&server.uri(), Duration::from_secs(5)).await;
+        assert!(!wait_for_daily_question(&app, Duration::from_millis(10)).await);
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
 }
@@ -0,0 +1,60 @@
+use prometheus::{Encoder, Histogram, HistogramOpts, IntCounter, IntGauge, Registry, TextEncoder};
+use std::sync::LazyLock;
+
+// Prometheus metrics for daily sends and LeetCode requests, served on /metrics
+pub struct Metrics {
+    registry: Registry,
+    pub sends_attempted: IntCounter,
+    pub sends_succeeded: IntCounter,
+    pub sends_failed: IntCounter,
+    pub fetch_latency: Histogram,
+    pub schema_errors: IntCounter,
+    pub subscribers: IntGauge,
+}
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(|| {
+    let registry = Registry::new();
+    let sends_attempted = IntCounter::new("leetcode_bot_sends_attempted_total", "Daily challenge sends attempted")
+        .expect("valid metric");
+    let sends_succeeded = IntCounter::new("leetcode_bot_sends_succeeded_total", "Daily challenge sends succeeded")
+        .expect("valid metric");
+    let sends_failed = IntCounter::new("leetcode_bot_sends_failed_total", "Daily challenge sends failed")
+        .expect("valid metric");
+    let fetch_latency = Histogram::with_opts(HistogramOpts::new(
+        "leetcode_bot_daily_fetch_seconds",
+        "Latency of fetching the daily question from LeetCode",
+    ))
+    .expect("valid metric");
+    let schema_errors = IntCounter::new(
+        "leetcode_bot_schema_errors_total",
+        "LeetCode daily responses that didn't match the expected schema",
+    )
+    .expect("valid metric");
+    let subscribers = IntGauge::new("leetcode_bot_subscribers", "Currently subscribed chats").expect("valid metric");
+
+    registry.register(Box::new(sends_attempted.clone())).expect("metric registered once");
+    registry.register(Box::new(sends_succeeded.clone())).expect("metric registered once");
+    registry.register(Box::new(sends_failed.clone())).expect("metric registered once");
+    registry.register(Box::new(fetch_latency.clone())).expect("metric registered once");
+    registry.register(Box::new(schema_errors.clone())).expect("metric registered once");
+    registry.register(Box::new(subscribers.clone())).expect("metric registered once");
+
+    Metrics {
+        registry,
+        sends_attempted,
+        sends_succeeded,
+        sends_failed,
+        fetch_latency,
+        schema_errors,
+        subscribers,
+    }
+});
+
+// Render all metrics in the Prometheus text format
+pub fn render() -> String {
+    let mut buffer = Vec::new();
+    if let Err(err) = TextEncoder::new().encode(&METRICS.registry.gather(), &mut buffer) {
+        tracing::error!(error = %err, "Failed to encode metrics.");
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
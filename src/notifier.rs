@@ -0,0 +1,152 @@
+use crate::error::BotError;
+use crate::leetcode::DailyQuestion;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+#[cfg(feature = "discord")]
+use chrono::NaiveTime;
+#[cfg(feature = "discord")]
+use tracing::info;
+
+// What a run sends: the daily, or the problem the admin featured with /override instead
+#[derive(Clone, Debug)]
+pub struct Challenge {
+    pub question: DailyQuestion,
+    pub featured: bool,
+}
+
+// Why the scheduler started a run
+#[derive(Clone, Copy, Debug)]
+pub enum Trigger {
+    // Delivery times within (since, now] passed since the scheduler's previous check
+    Passed { since: DateTime<Utc>, now: DateTime<Utc> },
+    // The bot just started and catches up on sends it missed while it was down
+    Startup { at: DateTime<Utc> },
+}
+
+// A channel the daily is sent through. Every minute the scheduler asks each notifier whether anything is due, then
+// fetches the daily once and hands it to all notifiers that are.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    // Shown in logs and the log channel
+    fn name(&self) -> &str;
+
+    // Whether anything is due for the trigger
+    async fn is_due(&self, trigger: Trigger) -> bool;
+
+    // Send the challenge to everything that's due for the trigger
+    async fn send_daily(&self, challenge: &Challenge, trigger: Trigger) -> Result<(), BotError>;
+}
+
+// Posts to a Discord channel through one of its webhooks
+#[cfg(feature = "discord")]
+pub struct DiscordWebhook {
+    http: reqwest::Client,
+    url: String,
+    // Posted once a day at TRIGGER_TIME in the server's timezone
+    trigger_time: NaiveTime,
+    // Log the daily instead of posting it
+    dry_run: bool,
+}
+
+#[cfg(feature = "discord")]
+impl DiscordWebhook {
+    pub fn new(url: String, trigger_time: NaiveTime, dry_run: bool) -> Self {
+        DiscordWebhook { http: reqwest::Client::new(), url, trigger_time, dry_run }
+    }
+}
+
+// Describe the challenge in Discord's Markdown
+#[cfg(feature = "discord")]
+fn format_discord(challenge: &Challenge) -> String {
+    let question = &challenge.question;
+    let label = if challenge.featured { "Featured" } else { "Today's" };
+    let difficulty = match question.difficulty {
+        Some(difficulty) => format!("{} ", difficulty.emoji()),
+        None => String::new(),
+    };
+    let mut text = format!("**{} LeetCode Challenge:** {}[{}](<{}>)", label, difficulty, question.title, question.link);
+    if let Some(difficulty) = question.difficulty {
+        text.push_str(&format!(" ({})", difficulty));
+    }
+    if !question.tags.is_empty() {
+        let tags: Vec<&str> = question.tags.iter().map(|tag| tag.name.as_str()).collect();
+        text.push_str(&format!("\nTopics: {}", tags.join(", ")));
+    }
+    text
+}
+
+#[cfg(feature = "discord")]
+#[async_trait]
+impl Notifier for DiscordWebhook {
+    fn name(&self) -> &str {
+        "Discord"
+    }
+
+    async fn is_due(&self, trigger: Trigger) -> bool {
+        // Nothing records whether a post went out, so there's nothing to catch up on at startup
+        match trigger {
+            Trigger::Passed { since, now } => crate::trigger_passed(since, now, self.trigger_time, None),
+            Trigger::Startup { .. } => false,
+        }
+    }
+
+    async fn send_daily(&self, challenge: &Challenge, _trigger: Trigger) -> Result<(), BotError> {
+        if self.dry_run {
+            info!(title = %challenge.question.title, "Dry run, not posting the daily to Discord.");
+            return Ok(());
+        }
+        // Problem titles can't ping anyone
+        let body = serde_json::json!({"content": format_discord(challenge), "allowed_mentions": {"parse": []}});
+        let response = self.http.post(&self.url).json(&body).send().await.map_err(BotError::Webhook)?;
+        response.error_for_status().map_err(BotError::Webhook)?;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "discord"))]
+mod tests {
+    use super::*;
+    use crate::leetcode::{Difficulty, TopicTag};
+    use wiremock::matchers::{body_string_contains, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn two_sum() -> Challenge {
+        let question = DailyQuestion {
+            title: "Two Sum".to_string(),
+            slug: "two-sum".to_string(),
+            link: "https://leetcode.com/problems/two-sum/".to_string(),
+            difficulty: Some(Difficulty::Easy),
+            ac_rate: None,
+            total_submissions: None,
+            tags: vec![TopicTag { name: "Array".to_string(), slug: "array".to_string() }],
+            paid_only: false,
+            code_snippets: Vec::new(),
+            content: None,
+            companies: Vec::new(),
+            hints: 0,
+        };
+        Challenge { question, featured: false }
+    }
+
+    #[tokio::test]
+    async fn posts_the_daily_to_the_webhook() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("[Two Sum](<https://leetcode.com/problems/two-sum/>) (Easy)"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let now = Utc::now();
+        let trigger = Trigger::Passed { since: now, now };
+        DiscordWebhook::new(server.uri(), NaiveTime::MIN, false).send_daily(&two_sum(), trigger).await.unwrap();
+
+        let failing = MockServer::start().await;
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(404)).mount(&failing).await;
+        let webhook = DiscordWebhook::new(failing.uri(), NaiveTime::MIN, false);
+        let err = webhook.send_daily(&two_sum(), trigger).await.unwrap_err();
+        assert!(matches!(err, BotError::Webhook(_)));
+        assert!(!err.is_transient());
+        assert!(!webhook.is_due(Trigger::Startup { at: now }).await);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::error::BotError;
+use crate::leetcode::{fetch_problem_index, LeetCodeClient, ProblemFilter, ProblemSummary};
+use chrono::{DateTime, Utc};
+use rand::seq::SliceRandom;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+// Summaries of every problem, so random picks don't page through LeetCode's problem list each time. About 3,500
+// problems of a few short strings each, well under a megabyte.
+#[derive(Clone, Default)]
+pub struct ProblemIndex {
+    loaded: Arc<RwLock<Option<Loaded>>>,
+}
+
+struct Loaded {
+    problems: Vec<ProblemSummary>,
+    fetched_at: DateTime<Utc>,
+}
+
+impl ProblemIndex {
+    // Replace the index with a fresh copy from LeetCode, keeping the current one if that fails
+    pub async fn refresh(&self, client: &LeetCodeClient) -> Result<usize, BotError> {
+        let problems = fetch_problem_index(client).await?;
+        if problems.is_empty() {
+            return Err(BotError::Parse("empty problem list".to_string()));
+        }
+        Ok(self.replace(problems).await)
+    }
+
+    async fn replace(&self, mut problems: Vec<ProblemSummary>) -> usize {
+        problems.shrink_to_fit();
+        let count = problems.len();
+        *self.loaded.write().await = Some(Loaded { problems, fetched_at: Utc::now() });
+        count
+    }
+
+    // A random problem matching the filter, `None` while the index hasn't been fetched yet
+    pub async fn pick(&self, filter: &ProblemFilter) -> Option<Option<ProblemSummary>> {
+        let loaded = self.loaded.read().await;
+        let candidates: Vec<&ProblemSummary> = loaded.as_ref()?.problems.iter().filter(|problem| filter.matches(problem)).collect();
+        Some(candidates.choose(&mut rand::thread_rng()).map(|problem| (*problem).clone()))
+    }
+
+    // Number of problems and when they were fetched, `None` while the index hasn't been fetched yet
+    pub async fn status(&self) -> Option<(usize, DateTime<Utc>)> {
+        self.loaded.read().await.as_ref().map(|loaded| (loaded.problems.len(), loaded.fetched_at))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::leetcode::Difficulty;
+    use std::collections::BTreeSet;
+
+    fn problem(slug: &str, difficulty: Difficulty, paid_only: bool, tags: &[&str]) -> ProblemSummary {
+        ProblemSummary {
+            slug: slug.to_string(),
+            title: slug.to_string(),
+            difficulty: Some(difficulty),
+            paid_only,
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn picks_only_matching_problems_once_loaded() {
+        let index = ProblemIndex::default();
+        assert_eq!(index.pick(&ProblemFilter::default()).await, None);
+        assert_eq!(index.status().await, None);
+
+        index
+            .replace(vec![
+                problem("two-sum", Difficulty::Easy, false, &["array"]),
+                problem("lru-cache", Difficulty::Medium, false, &["design"]),
+                problem("paid-hard", Difficulty::Hard, true, &["array"]),
+            ])
+            .await;
+        assert_eq!(index.status().await.map(|(count, _)| count), Some(3));
+
+        let hard = ProblemFilter { difficulties: BTreeSet::from([Difficulty::Hard]), ..ProblemFilter::default() };
+        assert_eq!(index.pick(&hard).await, Some(None));
+        let hard_with_paid = ProblemFilter { include_paid: true, ..hard };
+        assert_eq!(index.pick(&hard_with_paid).await.flatten().unwrap().slug, "paid-hard");
+        let design = ProblemFilter { tags: BTreeSet::from(["design".to_string()]), ..ProblemFilter::default() };
+        for _ in 0..10 {
+            assert_eq!(index.pick(&design).await.flatten().unwrap().slug, "lru-cache");
+        }
+    }
+}
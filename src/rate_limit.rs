@@ -0,0 +1,28 @@
+use tokio::sync::Mutex;
+use tokio::time::{sleep_until, Duration, Instant};
+
+// Spaces out outgoing messages evenly so the bot stays under Telegram's global flood limit
+pub struct RateLimiter {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn per_second(messages: u32) -> Self {
+        RateLimiter {
+            interval: Duration::from_secs(1) / messages.max(1),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    // Wait until the next send slot; concurrent callers get consecutive slots
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.interval;
+            slot
+        };
+        sleep_until(slot).await;
+    }
+}
@@ -0,0 +1,1239 @@
+use crate::leetcode::{DailyQuestion, Difficulty};
+use crate::ChatSettings;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use teloxide::types::ChatId;
+use tokio::fs as async_fs;
+use std::io::SeekFrom;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+use tracing::{debug, error, info, warn};
+
+// Chats that receive problems of each difficulty, set by the admin to fill topic channels
+pub type Routes = BTreeMap<Difficulty, BTreeSet<ChatId>>;
+
+// A problem the admin chose with /override to send instead of the daily
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FeaturedProblem {
+    pub question: DailyQuestion,
+    // UTC day of the scheduled run that took it, `None` until one did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<NaiveDate>,
+}
+
+// How sending the daily to one chat ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SendOutcome {
+    Sent,
+    Failed,
+    // Failed and unsubscribed because the chat can't be reached anymore
+    Removed,
+}
+
+// One chat's entry in the send log
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SendRecord {
+    pub at: DateTime<Utc>,
+    // UTC date of the run, what /log looks entries up by
+    pub date: NaiveDate,
+    pub chat_id: ChatId,
+    // Slug of the problem sent to the chat, `None` if LeetCode had none
+    pub slug: Option<String>,
+    pub outcome: SendOutcome,
+}
+
+// Days of send log kept in the database
+const SEND_LOG_DAYS: i64 = 30;
+
+// Size at which the send log file is rotated, keeping one older file around
+const MAX_SEND_LOG_BYTES: u64 = 5 * 1024 * 1024;
+
+// Persistence layer for subscriptions and the daily history, so the bot doesn't care where they're kept
+#[async_trait]
+pub trait Storage: Send + Sync {
+    // Load all subscribed chats with their settings
+    async fn load_chats(&self) -> Result<HashMap<ChatId, ChatSettings>, Box<dyn std::error::Error + Send + Sync>>;
+
+    // Add a chat or update its settings
+    async fn insert_chat(&self, chat_id: ChatId, settings: &ChatSettings);
+
+    // Remove a chat and its settings
+    async fn delete_chat(&self, chat_id: ChatId);
+
+    // Write out every chat and close the backend, used on shutdown
+    async fn flush(&self, all_chats: &HashMap<ChatId, ChatSettings>);
+
+    // Remember the daily question of a day, replacing an earlier entry for the same day
+    async fn record_daily(&self, date: NaiveDate, question: &DailyQuestion);
+
+    // Daily questions recorded on or after `since`, oldest first
+    async fn dailies_since(
+        &self,
+        since: NaiveDate,
+    ) -> Result<Vec<(NaiveDate, DailyQuestion)>, Box<dyn std::error::Error + Send + Sync>>;
+
+    // Append one chat's outcome to the send log, dropping old entries to keep it bounded
+    async fn record_send(&self, record: &SendRecord);
+
+    // Send log entries of one UTC day, in the order they were written
+    async fn sends_on(&self, date: NaiveDate) -> Result<Vec<SendRecord>, Box<dyn std::error::Error + Send + Sync>>;
+
+    // Chats waiting for a free subscriber slot, first in line first
+    async fn load_waitlist(&self) -> Result<Vec<ChatId>, Box<dyn std::error::Error + Send + Sync>>;
+
+    // Replace the stored waitlist
+    async fn save_waitlist(&self, waitlist: &[ChatId]);
+
+    // Whether the admin paused all scheduled sends, so maintenance survives a restart
+    async fn load_paused(&self) -> Result<bool, Box<dyn std::error::Error + Send + Sync>>;
+
+    // Remember whether scheduled sends are paused
+    async fn save_paused(&self, paused: bool);
+
+    // The admin's difficulty routing, empty if none was set
+    async fn load_routes(&self) -> Result<Routes, Box<dyn std::error::Error + Send + Sync>>;
+
+    // Replace the stored difficulty routing
+    async fn save_routes(&self, routes: &Routes);
+
+    // The admin's pending or current /override, if any
+    async fn load_featured(&self) -> Result<Option<FeaturedProblem>, Box<dyn std::error::Error + Send + Sync>>;
+
+    // Replace or, with `None`, remove the stored /override
+    async fn save_featured(&self, featured: Option<&FeaturedProblem>);
+}
+
+// Changes to one chat appended to the journal next to the chat IDs file
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum JournalEntry {
+    Insert { chat_id: ChatId, settings: Box<ChatSettings> },
+    Delete { chat_id: ChatId },
+}
+
+// Journal entries after which they're folded into the chat IDs file. A chat takes about 100 bytes in the file once
+// its settings and last broadcast are stored, so with 10,000 chats a rewrite is about 1 MB. Every change appends an
+// entry about as large as one chat, and that includes the last broadcast recorded for each chat a daily reaches: a run
+// to 10,000 chats appends 1 MB and compacts 20 times, about 20 MB, where rewriting the file per change took 10 GB.
+const MAX_JOURNAL_ENTRIES: usize = 500;
+
+// JSON file with all chats plus a journal of the changes since it was written, mirrored in memory
+pub struct FileStorage {
+    path: String,
+    chats: Mutex<HashMap<ChatId, ChatSettings>>,
+    // Entries in the journal, only changed while `chats` is locked
+    journal_entries: AtomicUsize,
+    // Held while appending to the send log, so concurrent sends don't interleave or race the rotation
+    send_log: Mutex<()>,
+    // Whether the latest save of the subscriptions failed, so a broken disk is only reported once
+    write_failing: AtomicBool,
+    // Called with a description when saving the subscriptions starts failing
+    write_alert: Option<Box<dyn Fn(String) + Send + Sync>>,
+    // Extra attempts at reading the chat IDs file at startup when it exists but can't be read
+    load_retries: u32,
+}
+
+impl FileStorage {
+    pub fn new(path: String) -> Self {
+        FileStorage {
+            path,
+            chats: Mutex::new(HashMap::new()),
+            journal_entries: AtomicUsize::new(0),
+            send_log: Mutex::new(()),
+            write_failing: AtomicBool::new(false),
+            write_alert: None,
+            load_retries: 0,
+        }
+    }
+
+    // Retry reading the chat IDs file at startup, for mounts that take a moment to come up
+    pub fn with_load_retries(mut self, retries: u32) -> Self {
+        self.load_retries = retries;
+        self
+    }
+
+    // Report failed saves of the subscriptions, since they would otherwise only live in memory until a restart
+    pub fn on_write_failure(mut self, alert: impl Fn(String) + Send + Sync + 'static) -> Self {
+        self.write_alert = Some(Box::new(alert));
+        self
+    }
+
+    // Append one change to the journal, folding it into the chat IDs file once it grows long; `chats` must be locked
+    async fn journal(&self, chats: &HashMap<ChatId, ChatSettings>, entry: &JournalEntry) {
+        let Ok(line) = serde_json::to_string(entry) else {
+            error!("Failed to serialize journal entry.");
+            return;
+        };
+        let path = journal_path(&self.path);
+        if let Err(err) = append_line(&path, line).await {
+            error!(path, error = %err, "Failed to append to the journal, saving all chats instead.");
+            self.compact(chats).await;
+            return;
+        }
+        self.track_write(true, "subscriptions");
+        if self.journal_entries.fetch_add(1, Ordering::SeqCst) + 1 >= MAX_JOURNAL_ENTRIES {
+            self.compact(chats).await;
+        }
+    }
+
+    // Rewrite the chat IDs file and empty the journal; replaying a leftover journal after a crash in between is harmless
+    async fn compact(&self, chats: &HashMap<ChatId, ChatSettings>) {
+        let saved = save_chat_ids(&self.path, chats).await;
+        self.track_write(saved, "subscriptions");
+        if !saved {
+            return;
+        }
+        let path = journal_path(&self.path);
+        match async_fs::remove_file(&path).await {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                error!(path, error = %err, "Failed to remove the compacted journal.");
+            }
+            _ => self.journal_entries.store(0, Ordering::SeqCst),
+        }
+    }
+
+    // Alert once when saving starts failing, and again only after a save succeeded in between
+    fn track_write(&self, saved: bool, what: &str) {
+        let was_failing = self.write_failing.swap(!saved, Ordering::SeqCst);
+        if saved && was_failing {
+            info!(path = self.path, "Saving works again.");
+        }
+        if saved || was_failing {
+            return;
+        }
+        if let Some(alert) = &self.write_alert {
+            alert(format!(
+                "Failed to save the {} to {}, changes are only kept in memory and will be lost on restart.",
+                what, self.path
+            ));
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for FileStorage {
+    async fn load_chats(&self) -> Result<HashMap<ChatId, ChatSettings>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut chats = self.chats.lock().await;
+        *chats = load_chat_ids(&self.path, self.load_retries).await?;
+        // Running with subscriptions that can't be saved would silently lose them on restart
+        check_writable(&self.path).await?;
+        // Start with an empty journal, so it only ever holds changes made since startup
+        if async_fs::try_exists(journal_path(&self.path)).await.unwrap_or(true) {
+            self.compact(&chats).await;
+        }
+        Ok(chats.clone())
+    }
+
+    async fn insert_chat(&self, chat_id: ChatId, settings: &ChatSettings) {
+        let mut chats = self.chats.lock().await;
+        chats.insert(chat_id, settings.clone());
+        self.journal(&chats, &JournalEntry::Insert { chat_id, settings: Box::new(settings.clone()) }).await;
+    }
+
+    async fn delete_chat(&self, chat_id: ChatId) {
+        let mut chats = self.chats.lock().await;
+        if chats.remove(&chat_id).is_none() {
+            return;
+        }
+        self.journal(&chats, &JournalEntry::Delete { chat_id }).await;
+    }
+
+    async fn flush(&self, all_chats: &HashMap<ChatId, ChatSettings>) {
+        let mut chats = self.chats.lock().await;
+        chats.clone_from(all_chats);
+        self.compact(&chats).await;
+    }
+
+    async fn record_daily(&self, date: NaiveDate, question: &DailyQuestion) {
+        let path = history_path(&self.path);
+        let mut history = load_history(&path).await;
+        history.insert(date, question.clone());
+        match serde_json::to_string(&history) {
+            Ok(data) => {
+                if let Err(err) = write_atomically(&path, data.as_bytes()).await {
+                    error!(path, error = %err, "Failed to save daily history.");
+                }
+            }
+            Err(err) => error!(error = %err, "Failed to serialize daily history."),
+        }
+    }
+
+    async fn dailies_since(
+        &self,
+        since: NaiveDate,
+    ) -> Result<Vec<(NaiveDate, DailyQuestion)>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(load_history(&history_path(&self.path))
+            .await
+            .into_iter()
+            .filter(|(date, _)| *date >= since)
+            .collect())
+    }
+
+    async fn record_send(&self, record: &SendRecord) {
+        let path = send_log_path(&self.path);
+        let _guard = self.send_log.lock().await;
+        if async_fs::metadata(&path).await.is_ok_and(|metadata| metadata.len() >= MAX_SEND_LOG_BYTES) {
+            if let Err(err) = async_fs::rename(&path, rotated_path(&path)).await {
+                error!(path, error = %err, "Failed to rotate send log.");
+            }
+        }
+        let Ok(line) = serde_json::to_string(record) else {
+            error!(chat_id = %record.chat_id, "Failed to serialize send log entry.");
+            return;
+        };
+        if let Err(err) = append_line(&path, line).await {
+            error!(path, error = %err, "Failed to append to send log.");
+        }
+    }
+
+    async fn sends_on(&self, date: NaiveDate) -> Result<Vec<SendRecord>, Box<dyn std::error::Error + Send + Sync>> {
+        let path = send_log_path(&self.path);
+        let mut records = Vec::new();
+        for path in [rotated_path(&path), path] {
+            let data = match async_fs::read_to_string(&path).await {
+                Ok(data) => data,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            };
+            // A crash mid-write can leave a partial last line, which is skipped like any other bad line
+            records.extend(
+                data.lines()
+                    .filter_map(|line| serde_json::from_str::<SendRecord>(line).ok())
+                    .filter(|record| record.date == date),
+            );
+        }
+        Ok(records)
+    }
+
+    async fn load_waitlist(&self) -> Result<Vec<ChatId>, Box<dyn std::error::Error + Send + Sync>> {
+        match async_fs::read_to_string(waitlist_path(&self.path)).await {
+            Ok(data) => Ok(serde_json::from_str(&data)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn save_waitlist(&self, waitlist: &[ChatId]) {
+        let path = waitlist_path(&self.path);
+        let saved = match serde_json::to_string(waitlist) {
+            Ok(data) => match write_atomically(&path, data.as_bytes()).await {
+                Ok(()) => true,
+                Err(err) => {
+                    error!(path, error = %err, "Failed to save waitlist.");
+                    false
+                }
+            },
+            Err(err) => {
+                error!(error = %err, "Failed to serialize waitlist.");
+                false
+            }
+        };
+        self.track_write(saved, "waitlist");
+    }
+
+    async fn load_paused(&self) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(async_fs::try_exists(paused_path(&self.path)).await?)
+    }
+
+    async fn save_paused(&self, paused: bool) {
+        let path = paused_path(&self.path);
+        let result = if paused {
+            async_fs::write(&path, b"").await
+        } else {
+            match async_fs::remove_file(&path).await {
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                result => result,
+            }
+        };
+        if let Err(err) = result {
+            error!(path, error = %err, "Failed to save the paused state.");
+        }
+    }
+
+    async fn load_routes(&self) -> Result<Routes, Box<dyn std::error::Error + Send + Sync>> {
+        match async_fs::read_to_string(routes_path(&self.path)).await {
+            Ok(data) => Ok(serde_json::from_str(&data)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Routes::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn save_routes(&self, routes: &Routes) {
+        let path = routes_path(&self.path);
+        let saved = match serde_json::to_string(routes) {
+            Ok(data) => match write_atomically(&path, data.as_bytes()).await {
+                Ok(()) => true,
+                Err(err) => {
+                    error!(path, error = %err, "Failed to save routes.");
+                    false
+                }
+            },
+            Err(err) => {
+                error!(error = %err, "Failed to serialize routes.");
+                false
+            }
+        };
+        self.track_write(saved, "routes");
+    }
+
+    async fn load_featured(&self) -> Result<Option<FeaturedProblem>, Box<dyn std::error::Error + Send + Sync>> {
+        match async_fs::read_to_string(featured_path(&self.path)).await {
+            Ok(data) => Ok(Some(serde_json::from_str(&data)?)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn save_featured(&self, featured: Option<&FeaturedProblem>) {
+        let path = featured_path(&self.path);
+        let result = match featured {
+            Some(featured) => match serde_json::to_string(featured) {
+                Ok(data) => write_atomically(&path, data.as_bytes()).await,
+                Err(err) => {
+                    error!(error = %err, "Failed to serialize the featured problem.");
+                    return;
+                }
+            },
+            None => match async_fs::remove_file(&path).await {
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                result => result,
+            },
+        };
+        if let Err(err) = result {
+            error!(path, error = %err, "Failed to save the featured problem.");
+        }
+    }
+}
+
+// SQLite database with one row per subscribed chat
+pub struct SqliteStorage {
+    pool: SqlitePool,
+}
+
+impl SqliteStorage {
+    // Open the database, importing the JSON file on first startup if the database is empty
+    pub async fn connect(
+        database_url: &str,
+        import_file_path: Option<&str>,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        info!("Connecting to database...");
+        let options = SqliteConnectOptions::from_str(database_url)?.create_if_missing(true);
+        let pool = SqlitePoolOptions::new().connect_with(options).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS subscriptions (
+                chat_id INTEGER PRIMARY KEY,
+                settings TEXT NOT NULL DEFAULT '{}'
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS daily_history (
+                date TEXT PRIMARY KEY,
+                question TEXT NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS send_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                at TEXT NOT NULL,
+                date TEXT NOT NULL,
+                chat_id INTEGER NOT NULL,
+                slug TEXT,
+                outcome TEXT NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS send_log_date ON send_log (date)")
+            .execute(&pool)
+            .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS waitlist (
+                position INTEGER PRIMARY KEY,
+                chat_id INTEGER NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS bot_state (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        let storage = SqliteStorage { pool };
+        if let Some(path) = import_file_path {
+            storage.import_file(path).await?;
+        }
+        Ok(storage)
+    }
+
+    // Copy subscriptions from a JSON file into an empty database
+    async fn import_file(&self, path: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM subscriptions")
+            .fetch_one(&self.pool)
+            .await?;
+        if count > 0 || !Path::new(path).exists() {
+            return Ok(());
+        }
+
+        info!(path, "Importing chat IDs from file into the database...");
+        let chats = load_chat_ids(path, 0).await?;
+        for (chat_id, settings) in &chats {
+            self.insert_chat(*chat_id, settings).await;
+        }
+        info!(count = chats.len(), "Imported chats.");
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn load_chats(&self) -> Result<HashMap<ChatId, ChatSettings>, Box<dyn std::error::Error + Send + Sync>> {
+        let rows = sqlx::query("SELECT chat_id, settings FROM subscriptions")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut chats = HashMap::new();
+        for row in rows {
+            let chat_id = ChatId(row.try_get("chat_id")?);
+            let settings: String = row.try_get("settings")?;
+            let settings = serde_json::from_str(&settings).unwrap_or_else(|err| {
+                warn!(%chat_id, error = %err, "Invalid settings stored for chat, using defaults.");
+                ChatSettings::default()
+            });
+            chats.insert(chat_id, settings);
+        }
+        Ok(chats)
+    }
+
+    async fn insert_chat(&self, chat_id: ChatId, settings: &ChatSettings) {
+        let Ok(settings) = serde_json::to_string(settings) else {
+            error!(%chat_id, "Failed to serialize settings for chat.");
+            return;
+        };
+        let result = sqlx::query(
+            "INSERT INTO subscriptions (chat_id, settings) VALUES (?, ?)
+            ON CONFLICT (chat_id) DO UPDATE SET settings = excluded.settings",
+        )
+        .bind(chat_id.0)
+        .bind(settings)
+        .execute(&self.pool)
+        .await;
+        if let Err(err) = result {
+            error!(%chat_id, error = %err, "Failed to save chat.");
+        }
+    }
+
+    async fn delete_chat(&self, chat_id: ChatId) {
+        let result = sqlx::query("DELETE FROM subscriptions WHERE chat_id = ?")
+            .bind(chat_id.0)
+            .execute(&self.pool)
+            .await;
+        if let Err(err) = result {
+            error!(%chat_id, error = %err, "Failed to delete chat.");
+        }
+    }
+
+    async fn flush(&self, all_chats: &HashMap<ChatId, ChatSettings>) {
+        for (chat_id, settings) in all_chats {
+            self.insert_chat(*chat_id, settings).await;
+        }
+        self.pool.close().await;
+    }
+
+    async fn record_daily(&self, date: NaiveDate, question: &DailyQuestion) {
+        let Ok(question) = serde_json::to_string(question) else {
+            error!(%date, "Failed to serialize daily question.");
+            return;
+        };
+        let result = sqlx::query(
+            "INSERT INTO daily_history (date, question) VALUES (?, ?)
+            ON CONFLICT (date) DO UPDATE SET question = excluded.question",
+        )
+        .bind(date.to_string())
+        .bind(question)
+        .execute(&self.pool)
+        .await;
+        if let Err(err) = result {
+            error!(%date, error = %err, "Failed to save daily question.");
+        }
+    }
+
+    async fn dailies_since(
+        &self,
+        since: NaiveDate,
+    ) -> Result<Vec<(NaiveDate, DailyQuestion)>, Box<dyn std::error::Error + Send + Sync>> {
+        let rows = sqlx::query("SELECT date, question FROM daily_history WHERE date >= ? ORDER BY date")
+            .bind(since.to_string())
+            .fetch_all(&self.pool)
+            .await?;
+        let mut dailies = Vec::new();
+        for row in rows {
+            let date: String = row.try_get("date")?;
+            let question: String = row.try_get("question")?;
+            match (date.parse(), serde_json::from_str(&question)) {
+                (Ok(date), Ok(question)) => dailies.push((date, question)),
+                _ => warn!(date, "Invalid daily question stored, skipping."),
+            }
+        }
+        Ok(dailies)
+    }
+
+    async fn record_send(&self, record: &SendRecord) {
+        let Ok(outcome) = serde_json::to_value(record.outcome) else {
+            return;
+        };
+        let result: Result<(), sqlx::Error> = async {
+            sqlx::query("INSERT INTO send_log (at, date, chat_id, slug, outcome) VALUES (?, ?, ?, ?, ?)")
+                .bind(record.at.to_rfc3339())
+                .bind(record.date.to_string())
+                .bind(record.chat_id.0)
+                .bind(&record.slug)
+                .bind(outcome.as_str())
+                .execute(&self.pool)
+                .await?;
+            sqlx::query("DELETE FROM send_log WHERE date < ?")
+                .bind((record.date - Duration::days(SEND_LOG_DAYS)).to_string())
+                .execute(&self.pool)
+                .await?;
+            Ok(())
+        }
+        .await;
+        if let Err(err) = result {
+            error!(chat_id = %record.chat_id, error = %err, "Failed to save send log entry.");
+        }
+    }
+
+    async fn sends_on(&self, date: NaiveDate) -> Result<Vec<SendRecord>, Box<dyn std::error::Error + Send + Sync>> {
+        let rows = sqlx::query("SELECT at, chat_id, slug, outcome FROM send_log WHERE date = ? ORDER BY id")
+            .bind(date.to_string())
+            .fetch_all(&self.pool)
+            .await?;
+        let mut records = Vec::new();
+        for row in rows {
+            let at: String = row.try_get("at")?;
+            let outcome: String = row.try_get("outcome")?;
+            match (DateTime::parse_from_rfc3339(&at), serde_json::from_value(outcome.clone().into())) {
+                (Ok(at), Ok(outcome)) => records.push(SendRecord {
+                    at: at.with_timezone(&Utc),
+                    date,
+                    chat_id: ChatId(row.try_get("chat_id")?),
+                    slug: row.try_get("slug")?,
+                    outcome,
+                }),
+                _ => warn!(at, outcome, "Invalid send log entry stored, skipping."),
+            }
+        }
+        Ok(records)
+    }
+
+    async fn load_waitlist(&self) -> Result<Vec<ChatId>, Box<dyn std::error::Error + Send + Sync>> {
+        let chat_ids: Vec<i64> = sqlx::query_scalar("SELECT chat_id FROM waitlist ORDER BY position")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(chat_ids.into_iter().map(ChatId).collect())
+    }
+
+    async fn save_waitlist(&self, waitlist: &[ChatId]) {
+        let result: Result<(), sqlx::Error> = async {
+            let mut transaction = self.pool.begin().await?;
+            sqlx::query("DELETE FROM waitlist").execute(&mut *transaction).await?;
+            for (position, chat_id) in waitlist.iter().enumerate() {
+                sqlx::query("INSERT INTO waitlist (position, chat_id) VALUES (?, ?)")
+                    .bind(position as i64)
+                    .bind(chat_id.0)
+                    .execute(&mut *transaction)
+                    .await?;
+            }
+            transaction.commit().await
+        }
+        .await;
+        if let Err(err) = result {
+            error!(error = %err, "Failed to save waitlist.");
+        }
+    }
+
+    async fn load_paused(&self) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let value: Option<String> = sqlx::query_scalar("SELECT value FROM bot_state WHERE key = 'paused'")
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(value.as_deref() == Some("true"))
+    }
+
+    async fn save_paused(&self, paused: bool) {
+        let result = sqlx::query(
+            "INSERT INTO bot_state (key, value) VALUES ('paused', ?)
+            ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+        )
+        .bind(paused.to_string())
+        .execute(&self.pool)
+        .await;
+        if let Err(err) = result {
+            error!(error = %err, "Failed to save the paused state.");
+        }
+    }
+
+    async fn load_routes(&self) -> Result<Routes, Box<dyn std::error::Error + Send + Sync>> {
+        let value: Option<String> = sqlx::query_scalar("SELECT value FROM bot_state WHERE key = 'routes'")
+            .fetch_optional(&self.pool)
+            .await?;
+        match value {
+            Some(value) => Ok(serde_json::from_str(&value)?),
+            None => Ok(Routes::new()),
+        }
+    }
+
+    async fn save_routes(&self, routes: &Routes) {
+        let value = match serde_json::to_string(routes) {
+            Ok(value) => value,
+            Err(err) => {
+                error!(error = %err, "Failed to serialize routes.");
+                return;
+            }
+        };
+        let result = sqlx::query(
+            "INSERT INTO bot_state (key, value) VALUES ('routes', ?)
+            ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+        )
+        .bind(value)
+        .execute(&self.pool)
+        .await;
+        if let Err(err) = result {
+            error!(error = %err, "Failed to save routes.");
+        }
+    }
+
+    async fn load_featured(&self) -> Result<Option<FeaturedProblem>, Box<dyn std::error::Error + Send + Sync>> {
+        let value: Option<String> = sqlx::query_scalar("SELECT value FROM bot_state WHERE key = 'featured'")
+            .fetch_optional(&self.pool)
+            .await?;
+        match value {
+            Some(value) => Ok(Some(serde_json::from_str(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn save_featured(&self, featured: Option<&FeaturedProblem>) {
+        let result = match featured.map(serde_json::to_string).transpose() {
+            Ok(Some(value)) => sqlx::query(
+                "INSERT INTO bot_state (key, value) VALUES ('featured', ?)
+                ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            )
+            .bind(value)
+            .execute(&self.pool)
+            .await,
+            Ok(None) => sqlx::query("DELETE FROM bot_state WHERE key = 'featured'").execute(&self.pool).await,
+            Err(err) => {
+                error!(error = %err, "Failed to serialize the featured problem.");
+                return;
+            }
+        };
+        if let Err(err) = result {
+            error!(error = %err, "Failed to save the featured problem.");
+        }
+    }
+}
+
+// Keeps everything in memory only, for tests that shouldn't touch the filesystem
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStorage {
+    pub chats: Mutex<HashMap<ChatId, ChatSettings>>,
+    history: Mutex<BTreeMap<NaiveDate, DailyQuestion>>,
+    pub waitlist: Mutex<Vec<ChatId>>,
+    sends: Mutex<Vec<SendRecord>>,
+    paused: Mutex<bool>,
+    routes: Mutex<Routes>,
+    featured: Mutex<Option<FeaturedProblem>>,
+}
+
+#[cfg(test)]
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn load_chats(&self) -> Result<HashMap<ChatId, ChatSettings>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.chats.lock().await.clone())
+    }
+
+    async fn insert_chat(&self, chat_id: ChatId, settings: &ChatSettings) {
+        self.chats.lock().await.insert(chat_id, settings.clone());
+    }
+
+    async fn delete_chat(&self, chat_id: ChatId) {
+        self.chats.lock().await.remove(&chat_id);
+    }
+
+    async fn flush(&self, all_chats: &HashMap<ChatId, ChatSettings>) {
+        self.chats.lock().await.clone_from(all_chats);
+    }
+
+    async fn record_daily(&self, date: NaiveDate, question: &DailyQuestion) {
+        self.history.lock().await.insert(date, question.clone());
+    }
+
+    async fn dailies_since(
+        &self,
+        since: NaiveDate,
+    ) -> Result<Vec<(NaiveDate, DailyQuestion)>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.history.lock().await.range(since..).map(|(date, question)| (*date, question.clone())).collect())
+    }
+
+    async fn record_send(&self, record: &SendRecord) {
+        self.sends.lock().await.push(record.clone());
+    }
+
+    async fn sends_on(&self, date: NaiveDate) -> Result<Vec<SendRecord>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.sends.lock().await.iter().filter(|record| record.date == date).cloned().collect())
+    }
+
+    async fn load_waitlist(&self) -> Result<Vec<ChatId>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.waitlist.lock().await.clone())
+    }
+
+    async fn save_waitlist(&self, waitlist: &[ChatId]) {
+        *self.waitlist.lock().await = waitlist.to_vec();
+    }
+
+    async fn load_paused(&self) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(*self.paused.lock().await)
+    }
+
+    async fn save_paused(&self, paused: bool) {
+        *self.paused.lock().await = paused;
+    }
+
+    async fn load_routes(&self) -> Result<Routes, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.routes.lock().await.clone())
+    }
+
+    async fn save_routes(&self, routes: &Routes) {
+        self.routes.lock().await.clone_from(routes);
+    }
+
+    async fn load_featured(&self) -> Result<Option<FeaturedProblem>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.featured.lock().await.clone())
+    }
+
+    async fn save_featured(&self, featured: Option<&FeaturedProblem>) {
+        *self.featured.lock().await = featured.cloned();
+    }
+}
+
+// The daily history lives next to the chat IDs file, e.g. chat_ids.history.json
+fn history_path(chat_ids_path: &str) -> String {
+    Path::new(chat_ids_path).with_extension("history.json").to_string_lossy().into_owned()
+}
+
+// The waitlist also lives next to the chat IDs file, e.g. chat_ids.waitlist.json
+fn waitlist_path(chat_ids_path: &str) -> String {
+    Path::new(chat_ids_path).with_extension("waitlist.json").to_string_lossy().into_owned()
+}
+
+// Changes since the chat IDs file was last written, e.g. chat_ids.journal.jsonl
+fn journal_path(chat_ids_path: &str) -> String {
+    Path::new(chat_ids_path).with_extension("journal.jsonl").to_string_lossy().into_owned()
+}
+
+// The difficulty routing also lives next to the chat IDs file, e.g. chat_ids.routes.json
+fn routes_path(chat_ids_path: &str) -> String {
+    Path::new(chat_ids_path).with_extension("routes.json").to_string_lossy().into_owned()
+}
+
+// The admin's /override also lives next to the chat IDs file while one is set, e.g. chat_ids.featured.json
+fn featured_path(chat_ids_path: &str) -> String {
+    Path::new(chat_ids_path).with_extension("featured.json").to_string_lossy().into_owned()
+}
+
+// Exists next to the chat IDs file while scheduled sends are paused, e.g. chat_ids.paused
+fn paused_path(chat_ids_path: &str) -> String {
+    Path::new(chat_ids_path).with_extension("paused").to_string_lossy().into_owned()
+}
+
+// The send log is appended to next to the chat IDs file, e.g. chat_ids.sends.jsonl
+fn send_log_path(chat_ids_path: &str) -> String {
+    Path::new(chat_ids_path).with_extension("sends.jsonl").to_string_lossy().into_owned()
+}
+
+// Where the send log is moved once it grows past MAX_SEND_LOG_BYTES, replacing the previous one
+fn rotated_path(send_log_path: &str) -> String {
+    format!("{}.1", send_log_path)
+}
+
+// Load the daily history file, starting over if it's missing or unreadable
+async fn load_history(path: &str) -> BTreeMap<NaiveDate, DailyQuestion> {
+    let Ok(data) = async_fs::read_to_string(path).await else {
+        return BTreeMap::new();
+    };
+    serde_json::from_str(&data).unwrap_or_else(|err| {
+        warn!(path, error = %err, "Daily history file is invalid, starting a new one.");
+        BTreeMap::new()
+    })
+}
+
+// Wait before the first retry of reading the chat IDs file, doubled for each further one
+const LOAD_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(2);
+
+// Read the chat IDs file, `None` if its directory exists but the file doesn't yet. Any other error, including a
+// missing directory, e.g. on a volume that isn't mounted yet, is retried after `delay`, doubled each time, and then
+// returned, since starting empty would unsubscribe everyone on the next save.
+async fn read_chat_ids_file(
+    file_path: &str,
+    retries: u32,
+    delay: std::time::Duration,
+) -> std::io::Result<Option<String>> {
+    let mut attempt = 0;
+    loop {
+        let err = match async_fs::read_to_string(file_path).await {
+            Ok(data) => return Ok(Some(data)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                let dir = parent_dir(file_path);
+                if async_fs::metadata(dir).await.is_ok_and(|metadata| metadata.is_dir()) {
+                    return Ok(None);
+                }
+                std::io::Error::new(err.kind(), format!("directory {} does not exist", dir.display()))
+            }
+            Err(err) => err,
+        };
+        if attempt >= retries {
+            error!(path = file_path, error = %err, "Failed to read the chat IDs file, refusing to start.");
+            return Err(err);
+        }
+        let delay = delay * 2u32.pow(attempt);
+        attempt += 1;
+        warn!(path = file_path, attempt, delay_secs = delay.as_secs(), error = %err, "Failed to read the chat IDs file, retrying.");
+        tokio::time::sleep(delay).await;
+    }
+}
+
+// Directory a file is in, the working directory for a bare file name
+fn parent_dir(file_path: &str) -> &Path {
+    match Path::new(file_path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+// Make sure the chat IDs file can be written, without creating or truncating it. Checked once loading found its
+// directory, which may only appear when a volume is mounted.
+async fn check_writable(file_path: &str) -> Result<(), String> {
+    if async_fs::try_exists(file_path).await.unwrap_or(false) {
+        return async_fs::OpenOptions::new()
+            .append(true)
+            .open(file_path)
+            .await
+            .map(|_| ())
+            .map_err(|err| format!("chat IDs file {} is not writable: {}", file_path, err));
+    }
+    let dir = parent_dir(file_path);
+    let probe = dir.join(".leetcode_bot_write_check");
+    async_fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&probe)
+        .await
+        .map_err(|err| format!("chat IDs file {}: directory {} is not writable: {}", file_path, dir.display(), err))?;
+    let _ = async_fs::remove_file(probe).await;
+    Ok(())
+}
+
+// Load chat IDs from the file, refusing to continue if the file is corrupted
+async fn load_chat_ids(
+    file_path: &str,
+    retries: u32,
+) -> Result<HashMap<ChatId, ChatSettings>, Box<dyn std::error::Error + Send + Sync>> {
+    info!("Loading chat IDs from file...");
+    if let Some(data) = read_chat_ids_file(file_path, retries, LOAD_RETRY_DELAY).await? {
+        debug!("Chat IDs file found.");
+        let parsed = serde_json::from_str(&data).or_else(|_| {
+            // Older versions stored a plain list of chat IDs without settings
+            serde_json::from_str::<HashSet<ChatId>>(&data)
+                .map(|ids| ids.into_iter().map(|id| (id, ChatSettings::default())).collect())
+        });
+        match parsed {
+            Ok(mut chats) => {
+                replay_journal(file_path, &mut chats).await?;
+                Ok(chats)
+            }
+            Err(err) => {
+                // Starting with an empty set would unsubscribe everyone on the next save
+                let backup_path = format!("{}.corrupt", file_path);
+                async_fs::copy(file_path, &backup_path).await?;
+                error!(path = file_path, backup_path, error = %err, "Chat IDs file is corrupted, refusing to start.");
+                Err(format!(
+                    "chat IDs file {} is corrupted ({}), a backup was saved to {}",
+                    file_path, err, backup_path
+                )
+                .into())
+            }
+        }
+    } else {
+        info!("Chat IDs file not found, creating a new one.");
+        let mut chats = HashMap::new();
+        replay_journal(file_path, &mut chats).await?;
+        Ok(chats)
+    }
+}
+
+// Add a line to the end of a file, creating it if needed
+async fn append_line(file_path: &str, mut line: String) -> std::io::Result<()> {
+    line.push('\n');
+    let mut file = async_fs::OpenOptions::new().create(true).read(true).append(true).open(file_path).await?;
+    // A crash mid-write can leave a partial last line, which the new line mustn't be glued to
+    let len = file.metadata().await?.len();
+    if len > 0 {
+        file.seek(SeekFrom::Start(len - 1)).await?;
+        if file.read_u8().await? != b'\n' {
+            line.insert(0, '\n');
+        }
+    }
+    file.write_all(line.as_bytes()).await?;
+    // Tokio finishes the write in the background, flushing makes it land before the caller moves on, and syncing
+    // gets it to the disk so the change survives a crash
+    file.flush().await?;
+    file.sync_data().await
+}
+
+// Apply the journal's changes on top of the chats loaded from the chat IDs file
+async fn replay_journal(
+    file_path: &str,
+    chats: &mut HashMap<ChatId, ChatSettings>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let path = journal_path(file_path);
+    let data = match async_fs::read_to_string(&path).await {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut applied = 0;
+    // A crash mid-write can leave a partial last line, that change never finished and is skipped
+    for entry in data.lines().filter_map(|line| serde_json::from_str::<JournalEntry>(line).ok()) {
+        match entry {
+            JournalEntry::Insert { chat_id, settings } => chats.insert(chat_id, *settings),
+            JournalEntry::Delete { chat_id } => chats.remove(&chat_id),
+        };
+        applied += 1;
+    }
+    info!(path, applied, "Replayed the subscriptions journal.");
+    Ok(())
+}
+
+// Temporary file next to the target, so the final rename stays on the same filesystem
+fn temp_path(file_path: &str) -> String {
+    format!("{}.tmp", file_path)
+}
+
+// Write the data to a temporary file and rename it over the target, so a crash never leaves a partial file
+async fn write_atomically(file_path: &str, data: &[u8]) -> std::io::Result<()> {
+    let temp_path = temp_path(file_path);
+    let mut file = async_fs::File::create(&temp_path).await?;
+    file.write_all(data).await?;
+    file.sync_all().await?;
+    async_fs::rename(&temp_path, file_path).await
+}
+
+// Save chat IDs to the file, returning whether it worked
+async fn save_chat_ids(file_path: &str, chat_ids: &HashMap<ChatId, ChatSettings>) -> bool {
+    debug!("Saving chat IDs to file...");
+    let Ok(data) = serde_json::to_string(chat_ids) else {
+        error!("Failed to serialize chat IDs.");
+        return false;
+    };
+    match write_atomically(file_path, data.as_bytes()).await {
+        Ok(()) => {
+            info!("Chat IDs saved.");
+            true
+        }
+        Err(err) => {
+            error!(path = file_path, error = %err, "Failed to save chat IDs.");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn corrupted_file_is_backed_up_and_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chat_ids.json");
+        let path = path.to_str().unwrap();
+        let corrupted = "{\"123\": {\"timezone\": ";
+        fs::write(path, corrupted).unwrap();
+
+        let storage = FileStorage::new(path.to_string());
+        assert!(storage.load_chats().await.is_err());
+
+        assert_eq!(fs::read_to_string(format!("{}.corrupt", path)).unwrap(), corrupted);
+        assert_eq!(fs::read_to_string(path).unwrap(), corrupted);
+    }
+
+    #[tokio::test]
+    async fn unreadable_file_fails_but_missing_file_starts_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("chat_ids.json");
+        assert!(FileStorage::new(missing.to_str().unwrap().to_string()).load_chats().await.unwrap().is_empty());
+
+        // A directory in place of the file reads with an error other than NotFound, like a mount that isn't up
+        let unreadable = dir.path().join("mount");
+        fs::create_dir(&unreadable).unwrap();
+        let storage = FileStorage::new(unreadable.to_str().unwrap().to_string());
+        assert!(storage.load_chats().await.is_err());
+
+        // Neither is a missing directory a fresh install
+        let unmounted = dir.path().join("volume").join("chat_ids.json");
+        assert!(FileStorage::new(unmounted.to_str().unwrap().to_string()).load_chats().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn waits_for_the_directory_to_appear() {
+        let dir = tempfile::tempdir().unwrap();
+        let volume = dir.path().join("volume");
+        let path = volume.join("chat_ids.json");
+        let mount = tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(30)).await;
+            fs::create_dir(&volume).unwrap();
+        });
+        let read = read_chat_ids_file(path.to_str().unwrap(), 3, std::time::Duration::from_millis(50)).await;
+        mount.await.unwrap();
+        assert!(read.unwrap().is_none());
+        assert!(check_writable(path.to_str().unwrap()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn send_log_survives_a_partial_line_and_filters_by_date() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chat_ids.json");
+        let storage = FileStorage::new(path.to_str().unwrap().to_string());
+        let date = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+        let record = |chat_id, date, outcome| SendRecord {
+            at: Utc::now(),
+            date,
+            chat_id: ChatId(chat_id),
+            slug: Some("two-sum".to_string()),
+            outcome,
+        };
+        storage.record_send(&record(1, date.pred_opt().unwrap(), SendOutcome::Sent)).await;
+        storage.record_send(&record(2, date, SendOutcome::Sent)).await;
+        storage.record_send(&record(3, date, SendOutcome::Removed)).await;
+
+        // A crash in the middle of a write leaves half a line behind
+        let log_path = send_log_path(path.to_str().unwrap());
+        let mut data = fs::read_to_string(&log_path).unwrap();
+        data.push_str("{\"at\": \"2024-03-10T");
+        fs::write(&log_path, data).unwrap();
+        storage.record_send(&record(4, date, SendOutcome::Failed)).await;
+
+        let records = storage.sends_on(date).await.unwrap();
+        assert_eq!(records.iter().map(|record| (record.chat_id, record.outcome)).collect::<Vec<_>>(), [
+            (ChatId(2), SendOutcome::Sent),
+            (ChatId(3), SendOutcome::Removed),
+            (ChatId(4), SendOutcome::Failed)
+        ]);
+    }
+
+    #[tokio::test]
+    async fn unwritable_path_alerts_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing").join("chat_ids.json");
+        let alerts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let storage = FileStorage::new(path.to_str().unwrap().to_string()).on_write_failure({
+            let alerts = Arc::clone(&alerts);
+            move |alert| alerts.lock().unwrap().push(alert)
+        });
+        storage.insert_chat(ChatId(1), &ChatSettings::default()).await;
+        storage.insert_chat(ChatId(2), &ChatSettings::default()).await;
+        assert_eq!(alerts.lock().unwrap().len(), 1);
+
+        // Once saving works again, the next failure is reported anew
+        fs::create_dir(dir.path().join("missing")).unwrap();
+        storage.insert_chat(ChatId(3), &ChatSettings::default()).await;
+        fs::remove_dir_all(dir.path().join("missing")).unwrap();
+        storage.delete_chat(ChatId(3)).await;
+        assert_eq!(alerts.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn paused_state_survives_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chat_ids.json").to_str().unwrap().to_string();
+        assert!(!FileStorage::new(path.clone()).load_paused().await.unwrap());
+
+        FileStorage::new(path.clone()).save_paused(true).await;
+        assert!(FileStorage::new(path.clone()).load_paused().await.unwrap());
+
+        let storage = FileStorage::new(path.clone());
+        storage.save_paused(false).await;
+        storage.save_paused(false).await;
+        assert!(!FileStorage::new(path).load_paused().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn changes_are_journaled_and_compacted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chat_ids.json").to_str().unwrap().to_string();
+        let storage = FileStorage::new(path.clone());
+        storage.load_chats().await.unwrap();
+        storage.insert_chat(ChatId(1), &ChatSettings::default()).await;
+        storage.insert_chat(ChatId(2), &ChatSettings { preview: true, ..ChatSettings::default() }).await;
+        storage.delete_chat(ChatId(1)).await;
+        // Chats that aren't stored have nothing to journal
+        storage.delete_chat(ChatId(3)).await;
+        assert_eq!(fs::read_to_string(journal_path(&path)).unwrap().lines().count(), 3);
+
+        // Only the journal was written, and a restart replays it
+        assert!(!Path::new(&path).exists());
+        let reloaded = FileStorage::new(path.clone()).load_chats().await.unwrap();
+        assert_eq!(reloaded.keys().collect::<Vec<_>>(), [&ChatId(2)]);
+        assert!(reloaded[&ChatId(2)].preview);
+        // Loading folded the journal into the chat IDs file
+        assert!(!Path::new(&journal_path(&path)).exists());
+        assert_eq!(load_chat_ids(&path, 0).await.unwrap(), reloaded);
+
+        let storage = FileStorage::new(path.clone());
+        storage.load_chats().await.unwrap();
+        for chat_id in 0..MAX_JOURNAL_ENTRIES as i64 {
+            storage.insert_chat(ChatId(100 + chat_id), &ChatSettings::default()).await;
+        }
+        assert!(!Path::new(&journal_path(&path)).exists());
+        let saved: HashMap<ChatId, ChatSettings> = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved.len(), MAX_JOURNAL_ENTRIES + 1);
+    }
+
+    #[tokio::test]
+    async fn interrupted_save_keeps_the_original_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chat_ids.json");
+        let path = path.to_str().unwrap();
+        let saved = HashMap::from([(ChatId(1), ChatSettings::default()), (ChatId(2), ChatSettings::default())]);
+        save_chat_ids(path, &saved).await;
+
+        // A crash halfway through the next save leaves only a truncated temporary file behind
+        let partial = serde_json::to_string(&HashMap::from([(ChatId(3), ChatSettings::default())])).unwrap();
+        fs::write(temp_path(path), &partial[..partial.len() / 2]).unwrap();
+        assert_eq!(load_chat_ids(path, 0).await.unwrap(), saved);
+
+        // The next save replaces the leftover temporary file
+        let updated = HashMap::from([(ChatId(3), ChatSettings::default())]);
+        save_chat_ids(path, &updated).await;
+        assert_eq!(load_chat_ids(path, 0).await.unwrap(), updated);
+        assert!(!Path::new(&temp_path(path)).exists());
+    }
+}
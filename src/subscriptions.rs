@@ -0,0 +1,668 @@
+use crate::i18n::Lang;
+use crate::leetcode::{DailyQuestion, Difficulty, ProblemFilter};
+use crate::metrics::METRICS;
+use crate::storage::Storage;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use teloxide::types::{ChatId, UserId};
+use tokio::sync::{Mutex, RwLock};
+
+// Per-chat preferences stored alongside the subscription
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChatSettings {
+    // IANA timezone name, `None` means the server's local timezone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<Tz>,
+    // Delivery time in the chat's timezone, `None` means the global TRIGGER_TIME
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trigger_time: Option<NaiveTime>,
+    // Weekdays the daily is sent on, Monday first; empty means every day
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<Weekday>,
+    // Accepted difficulties, empty means all
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub difficulties: BTreeSet<Difficulty>,
+    // Accepted topic tag slugs, empty means all
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    // Premium problems are replaced by free ones unless the chat wants to see them
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub show_premium: bool,
+    // Whether a random bonus problem is sent along with the daily
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub bonus: bool,
+    // Difficulty of the bonus problem, `None` means the chat's difficulty filter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bonus_difficulty: Option<Difficulty>,
+    // Whether the daily lists the companies that asked it, needs LEETCODE_SESSION
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub companies: bool,
+    // Whether the daily says how its difficulty compares to this month's dailies, e.g. "3rd Hard this month"
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub breakdown: bool,
+    // Whether the daily says how many hints the problem has, without showing them
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hints: bool,
+    // Whether the daily includes the start of the problem statement
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub preview: bool,
+    // Whether Telegram shows the rich preview of the problem link under the daily
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub link_preview: bool,
+    // LeetCode language slug whose starter code is added to the daily, e.g. python3
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_lang: Option<String>,
+    // Forum topic the chat subscribed from, `None` means the general thread
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<i32>,
+    // Custom daily message with {title}, {link} and {difficulty} placeholders, `None` means the default message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    // Language of the bot's messages in this chat
+    #[serde(default, skip_serializing_if = "Lang::is_default")]
+    pub lang: Lang,
+    // Pinning is on unless the chat turned it off
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pin_disabled: bool,
+    // Pins are silent unless the chat asked to be notified
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pin_notify: bool,
+    // Whether the previous challenge message is unpinned when the next one arrives
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub auto_unpin: bool,
+    // Whether auto-unpin also deletes the previous message
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub delete_previous: bool,
+    // ID of the last challenge message sent to the chat
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_message_id: Option<i32>,
+    // Whether each daily is posted as a reply to one pinned anchor message
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub reply_thread: bool,
+    // ID of the anchor message the dailies reply to, posted with the first daily after /thread on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor_message_id: Option<i32>,
+    // Whether the chat gets the weekly recap of daily problems
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub weekly_digest: bool,
+    // Whether the chat is reminded when nobody marked the daily as solved a few hours after it arrived
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub reminder: bool,
+    // Local date the last solve reminder was sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_reminder_date: Option<NaiveDate>,
+    // Local date daily sends resume after /snooze
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snoozed_until: Option<NaiveDate>,
+    // Local date the daily challenge was last delivered to the chat
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_broadcast_date: Option<NaiveDate>,
+    // Solving streaks of the chat's members
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub streaks: BTreeMap<UserId, Streak>,
+    // LeetCode usernames the chat's members bound with /setuser
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub leetcode_users: BTreeMap<UserId, String>,
+}
+
+// Consecutive days a user marked the daily problem as solved
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Streak {
+    pub count: u32,
+    pub last_solved: NaiveDate,
+}
+
+impl Streak {
+    // Streak length as of `today`, zero once a day was missed
+    pub fn current(&self, today: NaiveDate) -> u32 {
+        match today.pred_opt() {
+            Some(yesterday) if self.last_solved < yesterday => 0,
+            _ => self.count,
+        }
+    }
+}
+
+impl ChatSettings {
+    // Delivery time for this chat, falling back to the global trigger time
+    pub fn trigger_time(&self, default: NaiveTime) -> NaiveTime {
+        self.trigger_time.unwrap_or(default)
+    }
+
+    // Days left before daily sends resume, zero if the chat isn't snoozed
+    pub fn snooze_days_left(&self, today: NaiveDate) -> u32 {
+        self.snoozed_until
+            .map_or(0, |until| (until - today).num_days().clamp(0, i64::from(u32::MAX)) as u32)
+    }
+
+    // Whether the daily is sent to this chat on the given local date
+    pub fn delivers_on(&self, date: NaiveDate) -> bool {
+        self.days.is_empty() || self.days.contains(&date.weekday())
+    }
+
+    // Whether today's trigger time already passed at local time `now` without the challenge being delivered
+    pub fn missed_broadcast(&self, now: NaiveDateTime, default_trigger_time: NaiveTime) -> bool {
+        now.time() >= self.trigger_time(default_trigger_time)
+            && self.last_broadcast_date.is_none_or(|date| date < now.date())
+    }
+
+    // Whether a member marked the daily as solved on the given local date
+    pub fn solved_on(&self, date: NaiveDate) -> bool {
+        self.streaks.values().any(|streak| streak.last_solved == date)
+    }
+
+    // Whether the solve reminder is due at local time `now`: the daily arrived today at least `delay` ago,
+    // nobody solved it and no reminder was sent yet; a reminder that would fall on the next day is dropped
+    pub fn reminder_due(&self, now: NaiveDateTime, default_trigger_time: NaiveTime, delay: chrono::Duration) -> bool {
+        let today = now.date();
+        let remind_at = today.and_time(self.trigger_time(default_trigger_time)) + delay;
+        self.reminder
+            && self.snooze_days_left(today) == 0
+            && self.last_broadcast_date == Some(today)
+            && self.last_reminder_date.is_none_or(|date| date < today)
+            && remind_at.date() == today
+            && now >= remind_at
+            && !self.solved_on(today)
+    }
+
+    // Filter for random problems sent to this chat
+    pub fn problem_filter(&self) -> ProblemFilter {
+        ProblemFilter {
+            difficulties: self.difficulties.clone(),
+            tags: self.tags.clone(),
+            include_paid: self.show_premium,
+        }
+    }
+
+    // Filter for the bonus problem, `None` if the chat didn't enable it
+    pub fn bonus_filter(&self) -> Option<ProblemFilter> {
+        self.bonus.then(|| {
+            let mut filter = self.problem_filter();
+            if let Some(difficulty) = self.bonus_difficulty {
+                filter.difficulties = BTreeSet::from([difficulty]);
+            }
+            filter
+        })
+    }
+
+    // Whether a question matches the chat's difficulty, tag and premium filters; unknown fields always match
+    pub fn accepts(&self, question: &DailyQuestion) -> bool {
+        let difficulty_matches = self.difficulties.is_empty()
+            || question.difficulty.is_none_or(|difficulty| self.difficulties.contains(&difficulty));
+        let tags_match = self.tags.is_empty()
+            || question.tags.is_empty()
+            || question.tags.iter().any(|tag| self.tags.contains(&tag.slug));
+        difficulty_matches && tags_match && (self.show_premium || !question.paid_only)
+    }
+
+    // Mark today's problem as solved by a user, returning the streak and whether this was a new solve
+    pub fn record_solved(&mut self, user_id: UserId, today: NaiveDate) -> (u32, bool) {
+        match self.streaks.get_mut(&user_id) {
+            Some(streak) if streak.last_solved == today => (streak.count, false),
+            Some(streak) if streak.current(today) > 0 => {
+                streak.count += 1;
+                streak.last_solved = today;
+                (streak.count, true)
+            }
+            _ => {
+                self.streaks.insert(user_id, Streak { count: 1, last_solved: today });
+                (1, true)
+            }
+        }
+    }
+}
+
+// What happened when a chat asked to subscribe
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubscribeResult {
+    Subscribed,
+    // The chat was subscribed before, nothing changed
+    AlreadySubscribed,
+    // The bot is at capacity, so the chat waits at this 1-based position
+    Waitlisted { position: usize },
+}
+
+// What happened when a chat asked to unsubscribe
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnsubscribeResult {
+    // Whether the chat was subscribed, rather than only waitlisted or unknown
+    pub was_subscribed: bool,
+    // Waitlisted chats that took the freed slots
+    pub promoted: Vec<ChatId>,
+}
+
+// Every subscribed chat with its settings and the waitlist, as written by /export
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SubscriptionDump {
+    pub chats: HashMap<ChatId, ChatSettings>,
+    #[serde(default)]
+    pub waitlist: Vec<ChatId>,
+}
+
+// How /import combines an export with the current subscriptions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportMode {
+    // Add the imported chats, overwriting the settings of chats that are already subscribed
+    Merge,
+    // Drop every current chat and waitlist entry first
+    Replace,
+}
+
+// What an import changed
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub waitlisted: usize,
+    // Chats that were subscribed before a replace but not in the export
+    pub removed: usize,
+}
+
+// Subscribed chats kept in memory, with every change written through to storage
+pub struct Subscriptions {
+    // Read-locked by lookups and snapshots, so sends and settings replies don't queue behind each other;
+    // write-locked only while a change is made, storage is written once it's released
+    chats: RwLock<HashMap<ChatId, ChatSettings>>,
+    // Chats waiting for a free slot, in the order they tried to subscribe; always locked after `chats`
+    waitlist: Mutex<VecDeque<ChatId>>,
+    // Held while writing to storage, so writes don't interleave; taken before `chats` and `waitlist`, never while
+    // either is held
+    persist: Mutex<()>,
+    // Most chats that can be subscribed at once, `None` means no limit
+    max_subscribers: Option<usize>,
+    storage: Arc<dyn Storage>,
+}
+
+impl Subscriptions {
+    // Load the subscribed chats and the waitlist from storage
+    pub async fn load(
+        storage: Arc<dyn Storage>,
+        max_subscribers: Option<usize>,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let chats = storage.load_chats().await?;
+        let waitlist = storage.load_waitlist().await?;
+        METRICS.subscribers.set(chats.len() as i64);
+        Ok(Subscriptions {
+            chats: RwLock::new(chats),
+            waitlist: Mutex::new(waitlist.into()),
+            persist: Mutex::new(()),
+            max_subscribers,
+            storage,
+        })
+    }
+
+    pub async fn len(&self) -> usize {
+        self.chats.read().await.len()
+    }
+
+    // Number of subscribed chats and of distinct timezones among them, the server's own counting as one
+    pub async fn counts(&self) -> (usize, usize) {
+        let chats = self.chats.read().await;
+        let timezones: HashSet<Option<Tz>> = chats.values().map(|settings| settings.timezone).collect();
+        (chats.len(), timezones.len())
+    }
+
+    // Settings of a chat, or `None` if it isn't subscribed
+    pub async fn get(&self, chat_id: ChatId) -> Option<ChatSettings> {
+        self.chats.read().await.get(&chat_id).cloned()
+    }
+
+    // Copy of all subscribed chats with their settings, so callers don't hold the lock
+    pub async fn snapshot(&self) -> Vec<(ChatId, ChatSettings)> {
+        self.chats
+            .read()
+            .await
+            .iter()
+            .map(|(&chat_id, settings)| (chat_id, settings.clone()))
+            .collect()
+    }
+
+    // Copy of the chats and the waitlist taken under both locks, so no change lands between the two
+    pub async fn dump(&self) -> SubscriptionDump {
+        let chats = self.chats.read().await;
+        let waitlist = self.waitlist.lock().await;
+        SubscriptionDump {
+            chats: chats.clone(),
+            waitlist: waitlist.iter().copied().collect(),
+        }
+    }
+
+    // Restore an export, persisting every change; MAX_SUBSCRIBERS isn't applied since the admin decides what's imported
+    pub async fn import(&self, dump: SubscriptionDump, mode: ImportMode) -> ImportSummary {
+        let mut chats = self.chats.write().await;
+        let mut waitlist = self.waitlist.lock().await;
+        let mut summary = ImportSummary::default();
+        let mut changed = Vec::new();
+        if mode == ImportMode::Replace {
+            let removed: Vec<ChatId> = chats.keys().filter(|chat_id| !dump.chats.contains_key(chat_id)).copied().collect();
+            for chat_id in &removed {
+                chats.remove(chat_id);
+            }
+            summary.removed = removed.len();
+            changed.extend(removed);
+            waitlist.clear();
+        }
+        for (chat_id, settings) in dump.chats {
+            chats.insert(chat_id, settings);
+            changed.push(chat_id);
+            summary.imported += 1;
+        }
+        waitlist.retain(|chat_id| !chats.contains_key(chat_id));
+        for chat_id in dump.waitlist {
+            if !chats.contains_key(&chat_id) && !waitlist.contains(&chat_id) {
+                waitlist.push_back(chat_id);
+                summary.waitlisted += 1;
+            }
+        }
+        METRICS.subscribers.set(chats.len() as i64);
+        drop(waitlist);
+        drop(chats);
+        for chat_id in changed {
+            self.persist_chat(chat_id).await;
+        }
+        self.persist_waitlist().await;
+        summary
+    }
+
+    // Number of chats waiting for a free slot
+    pub async fn waitlist_len(&self) -> usize {
+        self.waitlist.lock().await.len()
+    }
+
+    // Subscribe a new chat, leaving an already subscribed one as it is; without `bypass_cap`
+    // a new chat is put on the waitlist once MAX_SUBSCRIBERS is reached
+    pub async fn subscribe(&self, chat_id: ChatId, bypass_cap: bool) -> SubscribeResult {
+        let mut chats = self.chats.write().await;
+        if chats.contains_key(&chat_id) {
+            return SubscribeResult::AlreadySubscribed;
+        }
+        let full = self.max_subscribers.is_some_and(|max| chats.len() >= max);
+        let mut waitlist = self.waitlist.lock().await;
+        if full && !bypass_cap {
+            drop(chats);
+            if let Some(index) = waitlist.iter().position(|&waiting| waiting == chat_id) {
+                return SubscribeResult::Waitlisted { position: index + 1 };
+            }
+            waitlist.push_back(chat_id);
+            let position = waitlist.len();
+            drop(waitlist);
+            self.persist_waitlist().await;
+            return SubscribeResult::Waitlisted { position };
+        }
+        chats.insert(chat_id, ChatSettings::default());
+        METRICS.subscribers.set(chats.len() as i64);
+        // An admin subscribing past the cap may have been waiting in line
+        let left_waitlist = leave_waitlist(&mut waitlist, chat_id);
+        drop(waitlist);
+        drop(chats);
+        self.persist_chat(chat_id).await;
+        if left_waitlist {
+            self.persist_waitlist().await;
+        }
+        SubscribeResult::Subscribed
+    }
+
+    // Unsubscribe a chat or take it off the waitlist, handing freed slots to waiting chats in order
+    pub async fn unsubscribe(&self, chat_id: ChatId) -> UnsubscribeResult {
+        let mut chats = self.chats.write().await;
+        let was_subscribed = chats.remove(&chat_id).is_some();
+        let mut waitlist = self.waitlist.lock().await;
+        let left_waitlist = leave_waitlist(&mut waitlist, chat_id);
+
+        let mut promoted = Vec::new();
+        if let Some(max) = self.max_subscribers {
+            while chats.len() < max {
+                let Some(next) = waitlist.pop_front() else {
+                    break;
+                };
+                chats.entry(next).or_default();
+                promoted.push(next);
+            }
+        }
+        METRICS.subscribers.set(chats.len() as i64);
+        drop(waitlist);
+        drop(chats);
+        // A /stop from a chat that isn't subscribed has nothing to delete
+        if was_subscribed {
+            self.persist_chat(chat_id).await;
+        }
+        for &next in &promoted {
+            self.persist_chat(next).await;
+        }
+        if left_waitlist || !promoted.is_empty() {
+            self.persist_waitlist().await;
+        }
+        UnsubscribeResult { was_subscribed, promoted }
+    }
+
+    // Write a chat's current settings to storage, or delete it if it's no longer subscribed. Called after the change
+    // released the locks; whichever write runs last stores the latest state, so concurrent changes can't leave it stale
+    async fn persist_chat(&self, chat_id: ChatId) {
+        let _persist = self.persist.lock().await;
+        let settings = self.chats.read().await.get(&chat_id).cloned();
+        match settings {
+            Some(settings) => self.storage.insert_chat(chat_id, &settings).await,
+            None => self.storage.delete_chat(chat_id).await,
+        }
+    }
+
+    // Write the current waitlist to storage, the same way as `persist_chat`
+    async fn persist_waitlist(&self) {
+        let _persist = self.persist.lock().await;
+        let mut waitlist = self.waitlist.lock().await.clone();
+        self.storage.save_waitlist(waitlist.make_contiguous()).await;
+    }
+
+    // Persist the current state, used on shutdown
+    pub async fn flush(&self) {
+        let _persist = self.persist.lock().await;
+        let chats = self.chats.read().await.clone();
+        self.storage.flush(&chats).await;
+    }
+
+    // Apply a settings change for a subscribed chat and persist it if anything changed
+    pub async fn update<R>(&self, chat_id: ChatId, update: impl FnOnce(&mut ChatSettings) -> R) -> Option<R> {
+        let mut chats = self.chats.write().await;
+        let settings = chats.get_mut(&chat_id)?;
+        let previous = settings.clone();
+        let result = update(settings);
+        let changed = *settings != previous;
+        drop(chats);
+        if changed {
+            self.persist_chat(chat_id).await;
+        }
+        Some(result)
+    }
+}
+
+// Remove a chat from the waitlist, returning whether it was on it
+fn leave_waitlist(waitlist: &mut VecDeque<ChatId>, chat_id: ChatId) -> bool {
+    let len = waitlist.len();
+    waitlist.retain(|&waiting| waiting != chat_id);
+    waitlist.len() != len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    fn at(date: &str, time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn missed_broadcast_compares_dates_after_the_trigger_time() {
+        let trigger_time = NaiveTime::from_hms_opt(10, 0, 0).unwrap();
+        let yesterday = NaiveDate::from_ymd_opt(2024, 3, 9);
+        let today = NaiveDate::from_ymd_opt(2024, 3, 10);
+
+        let sent_yesterday = ChatSettings { last_broadcast_date: yesterday, ..ChatSettings::default() };
+        assert!(sent_yesterday.missed_broadcast(at("2024-03-10", "10:00"), trigger_time));
+        assert!(sent_yesterday.missed_broadcast(at("2024-03-10", "23:59"), trigger_time));
+        assert!(!sent_yesterday.missed_broadcast(at("2024-03-10", "09:59"), trigger_time));
+
+        let sent_today = ChatSettings { last_broadcast_date: today, ..ChatSettings::default() };
+        assert!(!sent_today.missed_broadcast(at("2024-03-10", "12:00"), trigger_time));
+        assert!(sent_today.missed_broadcast(at("2024-03-11", "10:30"), trigger_time));
+
+        let never_sent = ChatSettings::default();
+        assert!(never_sent.missed_broadcast(at("2024-03-10", "12:00"), trigger_time));
+        assert!(!never_sent.missed_broadcast(at("2024-03-10", "08:00"), trigger_time));
+    }
+
+    #[test]
+    fn missed_broadcast_uses_the_chat_trigger_time() {
+        let settings = ChatSettings {
+            trigger_time: NaiveTime::from_hms_opt(18, 0, 0),
+            last_broadcast_date: NaiveDate::from_ymd_opt(2024, 3, 9),
+            ..ChatSettings::default()
+        };
+        let default_trigger_time = NaiveTime::from_hms_opt(10, 0, 0).unwrap();
+        assert!(!settings.missed_broadcast(at("2024-03-10", "12:00"), default_trigger_time));
+        assert!(settings.missed_broadcast(at("2024-03-10", "18:00"), default_trigger_time));
+    }
+
+    #[test]
+    fn reminder_is_due_once_after_the_delay_unless_solved() {
+        let trigger_time = NaiveTime::from_hms_opt(10, 0, 0).unwrap();
+        let today = NaiveDate::from_ymd_opt(2024, 3, 10);
+        let delay = chrono::Duration::hours(4);
+        let mut settings = ChatSettings { reminder: true, last_broadcast_date: today, ..ChatSettings::default() };
+        assert!(!settings.reminder_due(at("2024-03-10", "13:59"), trigger_time, delay));
+        assert!(settings.reminder_due(at("2024-03-10", "14:00"), trigger_time, delay));
+
+        settings.record_solved(UserId(1), today.unwrap());
+        assert!(!settings.reminder_due(at("2024-03-10", "14:00"), trigger_time, delay));
+
+        let reminded = ChatSettings { reminder: true, last_broadcast_date: today, last_reminder_date: today, ..ChatSettings::default() };
+        assert!(!reminded.reminder_due(at("2024-03-10", "15:00"), trigger_time, delay));
+        let not_sent = ChatSettings { reminder: true, last_broadcast_date: NaiveDate::from_ymd_opt(2024, 3, 9), ..ChatSettings::default() };
+        assert!(!not_sent.reminder_due(at("2024-03-10", "15:00"), trigger_time, delay));
+        let late = ChatSettings { trigger_time: NaiveTime::from_hms_opt(22, 0, 0), ..settings.clone() };
+        assert!(!late.reminder_due(at("2024-03-10", "23:59"), trigger_time, delay));
+    }
+
+    #[tokio::test]
+    async fn subscribe_and_unsubscribe_are_written_to_storage() {
+        let storage = Arc::new(MemoryStorage::default());
+        let subscriptions = Subscriptions::load(storage.clone(), None).await.unwrap();
+
+        subscriptions.subscribe(ChatId(1), false).await;
+        subscriptions.subscribe(ChatId(2), false).await;
+        assert_eq!(subscriptions.len().await, 2);
+        assert!(storage.chats.lock().await.contains_key(&ChatId(1)));
+
+        assert!(subscriptions.unsubscribe(ChatId(1)).await.was_subscribed);
+        assert!(!subscriptions.unsubscribe(ChatId(1)).await.was_subscribed);
+        assert_eq!(storage.chats.lock().await.keys().collect::<Vec<_>>(), [&ChatId(2)]);
+    }
+
+    #[tokio::test]
+    async fn subscribing_again_keeps_the_settings() {
+        let storage = Arc::new(MemoryStorage::default());
+        let subscriptions = Subscriptions::load(storage.clone(), None).await.unwrap();
+        subscriptions.subscribe(ChatId(1), false).await;
+        subscriptions.update(ChatId(1), |settings| settings.preview = true).await;
+
+        assert_eq!(subscriptions.subscribe(ChatId(1), false).await, SubscribeResult::AlreadySubscribed);
+        assert!(subscriptions.get(ChatId(1)).await.unwrap().preview);
+        assert!(storage.chats.lock().await[&ChatId(1)].preview);
+        assert_eq!(subscriptions.update(ChatId(2), |settings| settings.preview = true).await, None);
+    }
+
+    #[tokio::test]
+    async fn lookups_dont_wait_for_storage_writes() {
+        let storage = Arc::new(MemoryStorage::default());
+        let subscriptions = Arc::new(Subscriptions::load(storage.clone(), None).await.unwrap());
+        subscriptions.subscribe(ChatId(1), false).await;
+
+        // A slow write still in progress
+        let persist = subscriptions.persist.lock().await;
+        let update = tokio::spawn({
+            let subscriptions = Arc::clone(&subscriptions);
+            async move { subscriptions.update(ChatId(1), |settings| settings.preview = true).await }
+        });
+        let settings = tokio::time::timeout(std::time::Duration::from_secs(1), async {
+            loop {
+                match subscriptions.get(ChatId(1)).await {
+                    Some(settings) if settings.preview => break settings,
+                    _ => tokio::task::yield_now().await,
+                }
+            }
+        })
+        .await
+        .expect("lookup waited for the write");
+        assert!(settings.preview);
+        assert!(!storage.chats.lock().await[&ChatId(1)].preview);
+
+        drop(persist);
+        update.await.unwrap();
+        assert!(storage.chats.lock().await[&ChatId(1)].preview);
+    }
+
+    #[tokio::test]
+    async fn full_bot_waitlists_chats_and_promotes_them_in_order() {
+        let storage = Arc::new(MemoryStorage::default());
+        let subscriptions = Subscriptions::load(storage.clone(), Some(1)).await.unwrap();
+        assert_eq!(subscriptions.subscribe(ChatId(1), false).await, SubscribeResult::Subscribed);
+        assert_eq!(subscriptions.subscribe(ChatId(2), false).await, SubscribeResult::Waitlisted { position: 1 });
+        assert_eq!(subscriptions.subscribe(ChatId(3), false).await, SubscribeResult::Waitlisted { position: 2 });
+        assert_eq!(subscriptions.subscribe(ChatId(2), false).await, SubscribeResult::Waitlisted { position: 1 });
+        assert_eq!(*storage.waitlist.lock().await, [ChatId(2), ChatId(3)]);
+
+        // Admins skip the line
+        assert_eq!(subscriptions.subscribe(ChatId(9), true).await, SubscribeResult::Subscribed);
+        assert_eq!(subscriptions.len().await, 2);
+
+        // Two chats have to leave before the first waiting chat gets a slot
+        assert_eq!(subscriptions.unsubscribe(ChatId(9)).await.promoted, []);
+        let result = subscriptions.unsubscribe(ChatId(1)).await;
+        assert_eq!(result, UnsubscribeResult { was_subscribed: true, promoted: vec![ChatId(2)] });
+        assert!(storage.chats.lock().await.contains_key(&ChatId(2)));
+        assert_eq!(*storage.waitlist.lock().await, [ChatId(3)]);
+
+        // Leaving the waitlist doesn't free a slot
+        assert_eq!(subscriptions.unsubscribe(ChatId(3)).await, UnsubscribeResult::default());
+        assert_eq!(subscriptions.waitlist_len().await, 0);
+    }
+
+    #[tokio::test]
+    async fn dump_round_trips_through_json() {
+        let storage = Arc::new(MemoryStorage::default());
+        let subscriptions = Subscriptions::load(storage, Some(1)).await.unwrap();
+        subscriptions.subscribe(ChatId(1), false).await;
+        subscriptions.update(ChatId(1), |settings| settings.preview = true).await;
+        subscriptions.subscribe(ChatId(2), false).await;
+
+        let dump = subscriptions.dump().await;
+        assert!(dump.chats[&ChatId(1)].preview);
+        assert_eq!(dump.waitlist, [ChatId(2)]);
+        let json = serde_json::to_string(&dump).unwrap();
+        assert_eq!(serde_json::from_str::<SubscriptionDump>(&json).unwrap(), dump);
+    }
+
+    #[tokio::test]
+    async fn import_merges_or_replaces_and_persists() {
+        let storage = Arc::new(MemoryStorage::default());
+        let subscriptions = Subscriptions::load(storage.clone(), None).await.unwrap();
+        subscriptions.subscribe(ChatId(1), false).await;
+        let dump = SubscriptionDump {
+            chats: HashMap::from([(ChatId(2), ChatSettings { preview: true, ..ChatSettings::default() })]),
+            waitlist: vec![ChatId(1), ChatId(3)],
+        };
+
+        let json = serde_json::to_string(&dump).unwrap();
+        let summary = subscriptions.import(serde_json::from_str(&json).unwrap(), ImportMode::Merge).await;
+        assert_eq!(summary, ImportSummary { imported: 1, waitlisted: 1, removed: 0 });
+        assert_eq!(subscriptions.len().await, 2);
+        assert_eq!(*storage.waitlist.lock().await, [ChatId(3)]);
+
+        let summary = subscriptions.import(dump, ImportMode::Replace).await;
+        assert_eq!(summary, ImportSummary { imported: 1, waitlisted: 2, removed: 1 });
+        assert_eq!(storage.chats.lock().await.keys().collect::<Vec<_>>(), [&ChatId(2)]);
+        assert!(storage.chats.lock().await[&ChatId(2)].preview);
+        assert_eq!(*storage.waitlist.lock().await, [ChatId(1), ChatId(3)]);
+    }
+}
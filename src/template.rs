@@ -0,0 +1,77 @@
+use crate::leetcode::DailyQuestion;
+use teloxide::utils::html;
+
+// Placeholders a chat's message template may use
+pub const PLACEHOLDERS: &[&str] = &["title", "link", "difficulty"];
+
+// Longest template a chat can set, leaving room below Telegram's message limit
+pub const MAX_TEMPLATE_CHARS: usize = 1000;
+
+// Check that a template only uses known placeholders and has no unmatched braces
+pub fn validate(template: &str) -> Result<(), String> {
+    if template.chars().count() > MAX_TEMPLATE_CHARS {
+        return Err(format!("The template is too long, the limit is {} characters.", MAX_TEMPLATE_CHARS));
+    }
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        if rest[start..].starts_with('}') {
+            return Err("The template has a \"}\" without a matching \"{\".".to_string());
+        }
+        let Some(end) = rest[start..].find('}') else {
+            return Err("The template has a \"{\" without a matching \"}\".".to_string());
+        };
+        let name = &rest[start + 1..start + end];
+        if !PLACEHOLDERS.contains(&name) {
+            return Err(format!("Unknown placeholder {{{}}}.", name));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    Ok(())
+}
+
+// Fill a validated template with the question's fields, as HTML
+pub fn render(template: &str, question: &DailyQuestion) -> String {
+    let difficulty = question.difficulty.map_or_else(|| "Unknown".to_string(), |difficulty| difficulty.to_string());
+    html::escape(template)
+        .replace("{title}", &html::escape(&question.title))
+        .replace("{link}", &html::escape(&question.link))
+        .replace("{difficulty}", &difficulty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::leetcode::Difficulty;
+
+    #[test]
+    fn validate_rejects_unknown_placeholders_and_stray_braces() {
+        assert!(validate("@here {title} ({difficulty}): {link}").is_ok());
+        assert!(validate("No placeholders at all").is_ok());
+        assert_eq!(validate("{title} by {author}"), Err("Unknown placeholder {author}.".to_string()));
+        assert!(validate("{title").is_err());
+        assert!(validate("title}").is_err());
+        assert!(validate(&"x".repeat(MAX_TEMPLATE_CHARS + 1)).is_err());
+    }
+
+    #[test]
+    fn render_substitutes_and_escapes_fields() {
+        let question = DailyQuestion {
+            title: "Rock & Roll".to_string(),
+            slug: "rock-and-roll".to_string(),
+            link: "https://leetcode.com/problems/rock-and-roll/".to_string(),
+            difficulty: Some(Difficulty::Hard),
+            ac_rate: None,
+            total_submissions: None,
+            tags: Vec::new(),
+            paid_only: false,
+            code_snippets: Vec::new(),
+            content: None,
+            companies: Vec::new(),
+            hints: 0,
+        };
+        assert_eq!(
+            render("<b>Today</b>: {title} [{difficulty}] {link}", &question),
+            "&lt;b&gt;Today&lt;/b&gt;: Rock &amp; Roll [Hard] https://leetcode.com/problems/rock-and-roll/"
+        );
+    }
+}